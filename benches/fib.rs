@@ -0,0 +1,75 @@
+// Times the tree-walking interpreter and the bytecode vm on a recursive fib(25).
+// Run with `cargo bench`.
+use std::{
+    io::{sink, Cursor, Sink},
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+use interpreter::Interpreter;
+use parser::{ast::DeclRef, resolver::Resolver, Parser};
+use rustcore::Shared;
+use scanner::Scanner;
+use vm::{compiler::Compiler, Vm};
+
+const SOURCE: &str = "
+fun fib(n) {
+    if n < 2 return n;
+    return fib(n - 1) + fib(n - 2);
+}
+print fib(25);
+";
+
+const RUNS: u32 = 10;
+
+fn compile(source: &str) -> Vec<DeclRef> {
+    let tokens = Scanner::build_scanner(Cursor::new(source.as_bytes()))
+        .map(|token| Rc::new(token.expect("benchmark source scans")))
+        .collect();
+    let decls = Parser::new(tokens)
+        .parse()
+        .expect("benchmark source parses");
+    Resolver::new()
+        .resolve(&decls)
+        .expect("benchmark source resolves");
+    decls
+}
+
+fn main() {
+    let decls = compile(SOURCE);
+    report("tree-walker", || {
+        let mut interpreter: Interpreter<Sink> = Interpreter::new_with_out(Shared::new(sink()));
+        let start = Instant::now();
+        interpreter
+            .interpret(decls.clone())
+            .expect("benchmark runs without errors");
+        start.elapsed()
+    });
+
+    // compiling is left out of the timings, like resolving is for the tree-walker
+    let script = Compiler::new()
+        .compile(&decls)
+        .expect("benchmark source compiles");
+    report("vm", || {
+        let mut vm: Vm<Sink> = Vm::new_with_out(Shared::new(sink()));
+        let start = Instant::now();
+        vm.interpret(script.clone())
+            .expect("benchmark runs without errors");
+        start.elapsed()
+    });
+}
+
+fn report(backend: &str, mut run: impl FnMut() -> Duration) {
+    let mut timings: Vec<Duration> = (0..RUNS).map(|_| run()).collect();
+    timings.sort();
+
+    let total: Duration = timings.iter().sum();
+    println!(
+        "fib(25) on the {}: mean {:.2?}, median {:.2?}, min {:.2?} over {} runs",
+        backend,
+        total / RUNS,
+        timings[timings.len() / 2],
+        timings[0],
+        RUNS
+    );
+}
@@ -0,0 +1,38 @@
+class Doughnut {
+    cook() {
+        print "Fry until golden brown.";
+    }
+
+    describe() {
+        return "a " + this.kind + " doughnut";
+    }
+}
+
+class BostonCream < Doughnut {
+    init() {
+        this.kind = "Boston cream";
+    }
+}
+
+var d = BostonCream();
+d.cook();
+print d.describe();
+print BostonCream;
+
+// methods are found further up the chain
+class A {
+    method() {
+        print "A method";
+    }
+}
+class B < A {}
+class C < B {}
+C().method();
+
+// a subclass method shadows the superclass one
+class D < A {
+    method() {
+        print "D method";
+    }
+}
+D().method();
@@ -0,0 +1,20 @@
+class Counter {
+    start() {
+        this.count = 0;
+    }
+
+    increment(by) {
+        this.count = this.count + by;
+        return this.count;
+    }
+}
+
+var counter = Counter();
+counter.start();
+counter.increment(2);
+print counter.increment(3);
+
+// methods can be stored and called later
+var inc = counter.increment;
+inc(10);
+print counter.count;
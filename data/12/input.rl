@@ -0,0 +1,27 @@
+class Point {
+    init(x, y) {
+        this.x = x;
+        this.y = y;
+    }
+
+    sum() {
+        return this.x + this.y;
+    }
+}
+
+var p = Point(1, 2);
+print p.x;
+print p.y;
+print p.sum();
+
+// calling init again re-initialises the instance and returns it
+print p.init(3, 4);
+print p.sum();
+
+class Default {
+    init() {
+        this.name = "default";
+        return;
+    }
+}
+print Default().name;
@@ -0,0 +1,8 @@
+class Point {
+    init(x, y) {
+        this.x = x;
+        this.y = y;
+    }
+}
+
+var p = Point(1);
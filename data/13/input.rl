@@ -0,0 +1,13 @@
+class Person {
+    init(name) {
+        this.name = name;
+    }
+
+    greeting() {
+        return "Hello, " + this.name;
+    }
+}
+
+var person = Person("Jane");
+print person.name;
+print person.greeting();
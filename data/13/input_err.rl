@@ -0,0 +1,2 @@
+var number = 3;
+print number.field;
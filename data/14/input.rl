@@ -0,0 +1,15 @@
+class Box {}
+
+var box = Box();
+box.value = 1;
+print box.value;
+box.value = box.value + 1;
+print box.value;
+
+// assignment is an expression and evaluates to the assigned value
+print box.other = "other";
+
+var inner = Box();
+box.inner = inner;
+box.inner.value = "nested";
+print inner.value;
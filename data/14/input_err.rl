@@ -0,0 +1,2 @@
+var text = "text";
+text.length = 4;
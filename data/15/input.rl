@@ -0,0 +1,23 @@
+class Cake {
+    init(flavor) {
+        this.flavor = flavor;
+    }
+
+    taste() {
+        var adjective = "delicious";
+        print "The " + this.flavor + " cake is " + adjective + "!";
+    }
+
+    self() {
+        return this;
+    }
+}
+
+var cake = Cake("German chocolate");
+cake.taste();
+print cake.self() == cake;
+
+// methods keep their instance when passed around
+var taste = cake.taste;
+cake.flavor = "lemon";
+taste();
@@ -0,0 +1,5 @@
+fun notAMethod() {
+    print this;
+}
+
+notAMethod();
@@ -0,0 +1,60 @@
+class Doughnut {
+    cook() {
+        print "Fry until golden brown.";
+    }
+}
+
+class BostonCream < Doughnut {
+    cook() {
+        super.cook();
+        print "Pipe full of custard and coat with chocolate.";
+    }
+}
+
+BostonCream().cook();
+
+// super is resolved from the class containing the method, not the instance
+class A {
+    method() {
+        print "A method";
+    }
+}
+
+class B < A {
+    method() {
+        print "B method";
+    }
+
+    test() {
+        super.method();
+    }
+}
+
+class C < B {}
+
+C().test();
+
+// initializers can chain to the superclass
+class Base {
+    init(name) {
+        this.name = name;
+    }
+}
+
+class Derived < Base {
+    init(name) {
+        super.init(name);
+        this.greeting = "hello " + this.name;
+    }
+}
+
+print Derived("lox").greeting;
+
+// super methods can be stored and keep their instance
+class E < A {
+    get() {
+        return super.method;
+    }
+}
+var m = E().get();
+m();
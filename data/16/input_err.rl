@@ -0,0 +1,8 @@
+class Eclair {
+    cook() {
+        super.cook();
+        print "Pipe full of creme patissiere.";
+    }
+}
+
+Eclair().cook();
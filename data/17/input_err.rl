@@ -0,0 +1,16 @@
+var a = 1
+print a;
+
+var = 2;
+
+fun add(x, y) {
+    var sum = x + ;
+    return sum;
+}
+
+{
+    print a
+    print a;
+}
+
+print "done";
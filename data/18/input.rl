@@ -0,0 +1,28 @@
+var x = nil;
+print x;
+print x == nil;
+print nil == nil;
+print nil == false;
+print 1 != nil;
+
+var y;
+y = nil;
+print y;
+
+fun nothing() {
+    return;
+}
+
+fun implicit() {
+    var a = 1;
+}
+
+fun identity(v) {
+    return v;
+}
+
+print nothing();
+print implicit();
+print identity("value");
+print identity(nil);
+print identity(nil) == nothing();
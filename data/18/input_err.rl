@@ -0,0 +1,2 @@
+var x;
+print x;
@@ -0,0 +1,38 @@
+for (var i = 0; i < 3; i = i + 1) {
+    print i;
+}
+
+var j = 10;
+for (; j > 7; j = j - 1) {
+    print j;
+}
+print j;
+
+for (j = 0; j < 2;) {
+    print j;
+    j = j + 1;
+}
+
+var total = 0;
+for (var k = 1; ; k = k + 1) {
+    if k > 100 {
+        break;
+    }
+    total = total + k;
+}
+print total;
+
+for (var a = 0; a < 3; a = a + 1) {
+    for (var b = 0; b < 3; b = b + 1) {
+        if b == 2 {
+            break;
+        }
+        print a * 10 + b;
+    }
+}
+
+var i = "outer";
+for (var i = 0; i < 1; i = i + 1) {
+    print i;
+}
+print i;
@@ -0,0 +1,3 @@
+for (var i = 0; i < 3) {
+    print i;
+}
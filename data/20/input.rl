@@ -0,0 +1,39 @@
+// closures see the variable that was in scope where they were declared
+var a = "global";
+{
+    fun showA() {
+        print a;
+    }
+
+    showA();
+    var a = "block";
+    showA();
+    print a;
+}
+
+// shadowing in nested scopes
+var b = 1;
+{
+    var b = 2;
+    {
+        var b = 3;
+        print b;
+    }
+    print b;
+    b = 4;
+    print b;
+}
+print b;
+
+// assignment from a closure updates the captured variable
+fun makeAccumulator() {
+    var total = 0;
+    fun add(n) {
+        total = total + n;
+        print total;
+    }
+    return add;
+}
+var acc = makeAccumulator();
+acc(5);
+acc(10);
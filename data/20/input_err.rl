@@ -0,0 +1,15 @@
+print "not printed: errors are reported before running";
+{
+    var a = 1;
+    {
+        var a = a;
+    }
+    var a = 2;
+}
+return 1;
+
+class Point {
+    init() {
+        return 1;
+    }
+}
@@ -0,0 +1,22 @@
+var start = clock();
+print type(start);
+print clock() >= start;
+
+print str(12) + " apples";
+print str(true) + str(nil);
+print num("3.5") + 1;
+print num(" 42 ") * 2;
+print num(7);
+
+print type(1);
+print type("text");
+print type(false);
+print type(nil);
+print type(clock);
+class Point {}
+print type(Point);
+print type(Point());
+
+print len("hello");
+print len("");
+print len(str(1234));
@@ -0,0 +1,2 @@
+print len("ok");
+print num("twelve");
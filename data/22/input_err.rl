@@ -0,0 +1,18 @@
+class Counter {
+  init(limit) {
+    this.limit = limit;
+  }
+
+  step(n) {
+    return num(n);
+  }
+}
+
+fun count(counter, n) {
+  if n < counter.limit {
+    return count(counter, n + 1);
+  }
+  return counter.step("many");
+}
+
+print count(Counter(2), 0);
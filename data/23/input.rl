@@ -0,0 +1,76 @@
+// return from inside an if inside a while
+fun firstOver(limit) {
+  var i = 0;
+  while true {
+    if i * i > limit {
+      return i;
+    }
+    i = i + 1;
+  }
+  print "unreachable";
+}
+print firstOver(50);
+
+// return from nested loops ends the whole function
+fun findPair(sum) {
+  for (var a = 0; a < 10; a = a + 1) {
+    for (var b = 0; b < 10; b = b + 1) {
+      if a + b == sum and a * b == 12 {
+        return "" + str(a) + "," + str(b);
+      }
+    }
+  }
+  return "none";
+}
+print findPair(7);
+print findPair(100);
+
+// break only leaves the innermost loop
+fun countRows() {
+  var rows = 0;
+  for (var i = 0; i < 3; i = i + 1) {
+    while true {
+      break;
+    }
+    rows = rows + 1;
+  }
+  return rows;
+}
+print countRows();
+
+// a return in a loop called from a loop doesn't stop the caller
+fun firstSquare(from) {
+  while true {
+    for (var root = 0; root <= from; root = root + 1) {
+      if root * root == from {
+        return from;
+      }
+    }
+    from = from + 1;
+  }
+}
+var total = 0;
+for (var i = 2; i < 6; i = i + 1) {
+  total = total + firstSquare(i);
+}
+print total;
+
+// early return from an initializer still yields the instance
+class Box {
+  init(value) {
+    this.value = value;
+    if value < 0 {
+      this.value = 0;
+      return;
+    }
+    this.value = value * 2;
+  }
+}
+print Box(-5).value;
+print Box(4).value;
+
+// a function without a return statement gives nil
+fun nothing() {
+  while false {}
+}
+print nothing();
@@ -0,0 +1,29 @@
+var add = fun (a, b) { return a + b; };
+print add(1, 2);
+fun makeCounter() {
+  var i = 0;
+  return fun () { i = i + 1; return i; };
+}
+var c = makeCounter();
+c();
+print c();
+fun apply(f, x) { return f(x); }
+print apply(fun (n) { return n * 10; }, 4);
+fun () { print "iife"; }();
+print fun (x) { return -x; }(3);
+
+// lambdas capture the variables of the scope they are created in
+var adders = nil;
+{
+  var base = 100;
+  adders = fun (n) { return base + n; };
+}
+print adders(5);
+
+class Button {
+  init(label) {
+    this.label = label;
+    this.onClick = fun () { return "clicked " + this.label; };
+  }
+}
+print Button("ok").onClick();
@@ -0,0 +1,7 @@
+var check = fun (n) {
+  if n > 1 {
+    return n * nope;
+  }
+  return 1;
+};
+print check(2);
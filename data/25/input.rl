@@ -0,0 +1,5 @@
+var greeting = "hello";
+var count = 2;
+count = count * 3 + 1;
+if count > 5 and !false print greeting + " world";
+else print -count;
@@ -0,0 +1,12 @@
+fun counter() {
+    var n = 0;
+    return fun () {
+        n = n + 1;
+        return n;
+    };
+}
+var next = counter();
+for (var i = 0; i < 3; i = i + 1) {
+    if i == 1 { continue; }
+    print next();
+}
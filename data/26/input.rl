@@ -0,0 +1,17 @@
+// every counter refers to itself, so reference counting alone never frees it
+fun makeCounter() {
+    var i = 0;
+    fun count() {
+        i = i + 1;
+        if i < 3 count();
+        return i;
+    }
+    return count;
+}
+
+var total = 0;
+for (var n = 0; n < 100000; n = n + 1) {
+    var counter = makeCounter();
+    total = total + counter();
+}
+print total;
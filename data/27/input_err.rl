@@ -0,0 +1,4 @@
+fun deeper(n) {
+    return deeper(n + 1);
+}
+deeper(0);
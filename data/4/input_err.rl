@@ -1,7 +0,0 @@
-var a = 0;
-
-if a {
-    print "0";
-} else {
-    print "1";
-}
\ No newline at end of file
@@ -0,0 +1,33 @@
+var a = 0;
+if a {
+    print "0 is truthy";
+} else {
+    print "0 is falsey";
+}
+
+if "" {
+    print "empty string is truthy";
+}
+
+if nil {
+    print "nil is truthy";
+} else {
+    print "nil is falsey";
+}
+
+var b;
+b = nil;
+if !b {
+    print "!nil is true";
+}
+
+var count = 3;
+var done = false;
+while !done {
+    count = count - 1;
+    if count == 0 {
+        done = nil;
+        break;
+    }
+}
+print count;
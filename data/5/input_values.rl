@@ -0,0 +1,20 @@
+var input = nil;
+var name = input or "default";
+print name;
+
+print "first" or "second";
+print nil or false;
+print false or nil;
+print 1 and 2;
+print nil and 2;
+print false and nil;
+print "x" and nil;
+
+fun sideeffect() {
+    print "evaluated";
+    return true;
+}
+
+print true or sideeffect();
+print nil and sideeffect();
+print nil or sideeffect();
@@ -0,0 +1,5 @@
+var i = 0;
+while i < 200000 {
+    i = i + 1;
+}
+print i;
@@ -0,0 +1,29 @@
+// the increment still runs when the body continues
+for (var i = 0; i < 6; i = i + 1) {
+    if i == 1 or i == 4 {
+        continue;
+    }
+    print i;
+}
+
+var n = 0;
+var skipped = 0;
+while n < 6 {
+    n = n + 1;
+    if n > 2 and n < 5 {
+        skipped = skipped + 1;
+        continue;
+    }
+    print n;
+}
+print skipped;
+
+// continue only affects the innermost loop
+for (var row = 0; row < 3; row = row + 1) {
+    for (var col = 0; col < 3; col = col + 1) {
+        if col == row {
+            continue;
+        }
+        print "" + str(row) + str(col);
+    }
+}
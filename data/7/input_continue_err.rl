@@ -0,0 +1,10 @@
+fun skip() {
+    continue;
+}
+
+while true {
+    fun inner() {
+        continue;
+    }
+    break;
+}
@@ -0,0 +1,14 @@
+fun fib(n) {
+    if n < 2 return n;
+    return fib(n - 1) + fib(n - 2);
+}
+
+print fib(15);
+
+fun countdown(n) {
+    if n < 0 return;
+    print n;
+    countdown(n - 1);
+}
+
+countdown(3);
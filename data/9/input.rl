@@ -0,0 +1,21 @@
+class Breakfast {
+    cook() {
+        print "Eggs a-fryin'!";
+    }
+
+    serve(who) {
+        print "Enjoy your breakfast, " + who + ".";
+    }
+}
+
+print Breakfast;
+var breakfast = Breakfast();
+print breakfast;
+breakfast.cook();
+breakfast.serve("Dear Reader");
+
+class Empty {}
+var a = Empty();
+var b = Empty();
+print a == a;
+print a == b;
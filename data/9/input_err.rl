@@ -0,0 +1,3 @@
+class Bagel {}
+var bagel = Bagel();
+bagel.spread();
@@ -1,15 +1,116 @@
-use parser::utils::Visitor;
-use std::fmt::{Debug, Display};
+use parser::{ast::FunDecl, utils::Visitor};
+use rustcore::{Trace, Tracer};
+use std::{
+    fmt::{Debug, Display},
+    rc::Rc,
+};
 
-use crate::{environment::EnvironmentRef, result::IResult};
+use crate::{
+    environment::{Environment, EnvironmentRef},
+    error::{RuntimeError, Unwind},
+    result::IResult,
+};
+
+pub type NativeFn = dyn Fn(Vec<IResult>) -> Result<IResult, RuntimeError>;
+
+pub enum Body {
+    // a function implemented in rust
+    Native(Rc<NativeFn>),
+    // a user defined function and the environment it was declared in
+    Function {
+        decl: Rc<FunDecl>,
+        closure: EnvironmentRef,
+        is_initializer: bool,
+    },
+}
 
 pub struct LoxCallable {
+    // shown in backtraces; natives have none as their errors point at the call
+    pub name: Option<Rc<str>>,
     pub arity: usize,
-    //pub declaring_env: EnvironmentRef,
-    pub call: Box<dyn FnMut(&mut dyn VisitorEnvironmentAware, Vec<IResult>) -> IResult>,
+    pub body: Body,
+}
+
+impl LoxCallable {
+    // a function implemented in rust; it gets the evaluated arguments, already
+    // checked against the arity
+    pub fn new_native<F>(arity: usize, function: F) -> Self
+    where
+        F: Fn(Vec<IResult>) -> Result<IResult, RuntimeError> + 'static,
+    {
+        LoxCallable {
+            name: Option::None,
+            arity,
+            body: Body::Native(Rc::new(function)),
+        }
+    }
+
+    // a user defined function; its body runs in a fresh scope nested in the closure
+    // environment it was declared in. Initializers always return the bound instance.
+    pub fn new_function(decl: Rc<FunDecl>, closure: EnvironmentRef, is_initializer: bool) -> Self {
+        LoxCallable {
+            name: Some(decl.identifier.lexeme.as_str().into()),
+            arity: decl.params.len(),
+            body: Body::Function {
+                decl,
+                closure,
+                is_initializer,
+            },
+        }
+    }
+
+    // the rust function of a native, callable without an interpreter
+    pub fn native(&self) -> Option<&Rc<NativeFn>> {
+        match &self.body {
+            Body::Native(native) => Some(native),
+            Body::Function { .. } => Option::None,
+        }
+    }
+
+    pub fn call(
+        &self,
+        visitor: &mut dyn VisitorEnvironmentAware,
+        params: Vec<IResult>,
+    ) -> Result<IResult, RuntimeError> {
+        let (decl, closure, is_initializer) = match &self.body {
+            Body::Native(native) => return native(params),
+            Body::Function {
+                decl,
+                closure,
+                is_initializer,
+            } => (decl, closure, *is_initializer),
+        };
+
+        let original_env = visitor.get_environment();
+        visitor.set_environment(Environment::new_with_parent(closure.clone()));
+
+        for param in params {
+            visitor.get_environment().borrow_mut().define(param);
+        }
+
+        let result = visitor.visit_block_stmt(&decl.body);
+        visitor.set_environment(original_env);
+        match result {
+            Ok(_) | Err(Unwind::Return(_)) if is_initializer => {
+                Ok(closure.get_at(0, 0).unwrap().as_ref().clone())
+            }
+            // the body finished without reaching a return statement
+            Ok(_) => Ok(IResult::Nil),
+            Err(Unwind::Return(value)) => Ok(value),
+            Err(unwind) => Err(unwind.into_error()),
+        }
+    }
+}
+
+impl Trace for LoxCallable {
+    fn trace(&self, tracer: &mut Tracer) {
+        if let Body::Function { closure, .. } = &self.body {
+            tracer.edge(closure);
+        }
+    }
 }
 
-pub trait VisitorEnvironmentAware: Visitor<IResult> + EnvironmentAware {}
+pub trait VisitorEnvironmentAware: Visitor<Result<IResult, Unwind>> + EnvironmentAware {}
 
 pub trait EnvironmentAware {
     fn get_environment(&self) -> EnvironmentRef;
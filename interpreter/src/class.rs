@@ -0,0 +1,130 @@
+use std::{collections::HashMap, fmt::Debug, rc::Rc};
+
+use parser::ast::FunDecl;
+use rustcore::{Gc, Trace, Tracer};
+
+use crate::{
+    callable::LoxCallable,
+    environment::{Environment, EnvironmentRef},
+    result::IResult,
+};
+
+pub struct LoxClass {
+    pub name: String,
+    superclass: Option<Gc<LoxClass>>,
+    methods: HashMap<String, Rc<FunDecl>>,
+    // environment the class was declared in; methods close over it
+    closure: EnvironmentRef,
+}
+
+impl LoxClass {
+    pub fn new(
+        name: &str,
+        superclass: Option<Gc<LoxClass>>,
+        methods: Vec<Rc<FunDecl>>,
+        closure: EnvironmentRef,
+    ) -> Self {
+        Self {
+            name: name.to_string(),
+            superclass,
+            methods: methods
+                .into_iter()
+                .map(|m| (m.identifier.lexeme.clone(), m))
+                .collect(),
+            closure,
+        }
+    }
+
+    // methods not defined on the class are looked up along the superclass chain
+    pub fn find_method(&self, name: &str) -> Option<Rc<FunDecl>> {
+        match self.methods.get(name) {
+            Some(method) => Some(method.clone()),
+            None => self.superclass.as_ref()?.find_method(name),
+        }
+    }
+
+    // calling the class runs init (if any) with the call arguments
+    pub fn arity(&self) -> usize {
+        self.find_method("init").map_or(0, |init| init.params.len())
+    }
+
+    // a callable for the named method where `this` refers to the given instance.
+    // Inherited methods close over the environment of the class defining them.
+    pub fn bind(&self, name: &str, instance: IResult) -> Option<LoxCallable> {
+        let Some(method) = self.methods.get(name) else {
+            return self.superclass.as_ref()?.bind(name, instance);
+        };
+        let environment = Environment::new_with_parent(self.closure.clone());
+        environment.borrow_mut().define(instance);
+        Some(LoxCallable::new_function(
+            method.clone(),
+            environment,
+            name == "init",
+        ))
+    }
+}
+
+impl Trace for LoxClass {
+    fn trace(&self, tracer: &mut Tracer) {
+        if let Some(superclass) = &self.superclass {
+            tracer.edge(superclass);
+        }
+        tracer.edge(&self.closure);
+    }
+}
+
+impl Debug for LoxClass {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "LoxClass {{ name: {} }}", self.name)
+    }
+}
+
+// classes and instances compare by identity
+impl PartialEq for LoxClass {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+pub struct LoxInstance {
+    pub class: Gc<LoxClass>,
+    fields: HashMap<String, IResult>,
+}
+
+impl LoxInstance {
+    pub fn new(class: Gc<LoxClass>) -> Self {
+        Self {
+            class,
+            fields: HashMap::new(),
+        }
+    }
+
+    pub fn get_field(&self, name: &str) -> Option<IResult> {
+        self.fields.get(name).cloned()
+    }
+
+    pub fn set_field(&mut self, name: &str, value: IResult) {
+        self.fields.insert(name.to_string(), value);
+    }
+}
+
+impl Trace for LoxInstance {
+    fn trace(&self, tracer: &mut Tracer) {
+        tracer.edge(&self.class);
+        for value in self.fields.values() {
+            value.trace(tracer);
+        }
+    }
+}
+
+impl Debug for LoxInstance {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "LoxInstance {{ class: {} }}", self.class.name)
+    }
+}
+
+impl PartialEq for LoxInstance {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
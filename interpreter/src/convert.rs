@@ -0,0 +1,201 @@
+use std::rc::Rc;
+
+use crate::{
+    callable::LoxCallable,
+    error::{RuntimeError, RuntimeErrorKind},
+    result::IResult,
+};
+
+// Conversions between Lox values and Rust types, used to write native functions
+// with typed signatures:
+//
+//     registry.register("add", native(|a: f64, b: f64| a + b));
+//
+// Arguments of the wrong type raise a runtime error pointing at the call.
+pub trait FromLox: Sized {
+    fn from_lox(value: IResult) -> Result<Self, RuntimeError>;
+}
+
+pub trait IntoLox {
+    fn into_lox(self) -> IResult;
+}
+
+fn mismatch(expected: &str, value: &IResult) -> RuntimeError {
+    RuntimeError::native(
+        RuntimeErrorKind::InvalidOperand,
+        format!("expected {} but got {}", expected, value.type_name()).as_str(),
+    )
+}
+
+impl FromLox for IResult {
+    fn from_lox(value: IResult) -> Result<Self, RuntimeError> {
+        Ok(value)
+    }
+}
+
+impl IntoLox for IResult {
+    fn into_lox(self) -> IResult {
+        self
+    }
+}
+
+impl FromLox for f64 {
+    fn from_lox(value: IResult) -> Result<Self, RuntimeError> {
+        match value {
+            IResult::Number(n) => Ok(n),
+            other => Err(mismatch("number", &other)),
+        }
+    }
+}
+
+impl IntoLox for f64 {
+    fn into_lox(self) -> IResult {
+        IResult::Number(self)
+    }
+}
+
+// only booleans convert; use `IResult::is_truthy` for truthiness
+impl FromLox for bool {
+    fn from_lox(value: IResult) -> Result<Self, RuntimeError> {
+        match value {
+            IResult::Bool(b) => Ok(b),
+            other => Err(mismatch("bool", &other)),
+        }
+    }
+}
+
+impl IntoLox for bool {
+    fn into_lox(self) -> IResult {
+        IResult::Bool(self)
+    }
+}
+
+impl FromLox for String {
+    fn from_lox(value: IResult) -> Result<Self, RuntimeError> {
+        match value {
+            IResult::String(s) => Ok(Rc::unwrap_or_clone(s)),
+            other => Err(mismatch("string", &other)),
+        }
+    }
+}
+
+impl IntoLox for String {
+    fn into_lox(self) -> IResult {
+        IResult::String(Rc::new(self))
+    }
+}
+
+impl IntoLox for &str {
+    fn into_lox(self) -> IResult {
+        IResult::String(Rc::new(self.to_string()))
+    }
+}
+
+// nil converts to None
+impl<T: FromLox> FromLox for Option<T> {
+    fn from_lox(value: IResult) -> Result<Self, RuntimeError> {
+        match value {
+            IResult::Nil => Ok(None),
+            other => T::from_lox(other).map(Some),
+        }
+    }
+}
+
+impl<T: IntoLox> IntoLox for Option<T> {
+    fn into_lox(self) -> IResult {
+        self.map_or(IResult::Nil, T::into_lox)
+    }
+}
+
+impl<T: FromLox> FromLox for Vec<T> {
+    fn from_lox(value: IResult) -> Result<Self, RuntimeError> {
+        match value {
+            IResult::List(items) => items.iter().cloned().map(T::from_lox).collect(),
+            other => Err(mismatch("list", &other)),
+        }
+    }
+}
+
+impl<T: IntoLox> IntoLox for Vec<T> {
+    fn into_lox(self) -> IResult {
+        IResult::List(Rc::new(self.into_iter().map(T::into_lox).collect()))
+    }
+}
+
+impl FromLox for () {
+    fn from_lox(value: IResult) -> Result<Self, RuntimeError> {
+        match value {
+            IResult::Nil => Ok(()),
+            other => Err(mismatch("nil", &other)),
+        }
+    }
+}
+
+impl IntoLox for () {
+    fn into_lox(self) -> IResult {
+        IResult::Nil
+    }
+}
+
+// what a native function may return: a value, or a value or an error
+pub trait NativeResult {
+    fn into_result(self) -> Result<IResult, RuntimeError>;
+}
+
+impl<T: IntoLox> NativeResult for T {
+    fn into_result(self) -> Result<IResult, RuntimeError> {
+        Ok(self.into_lox())
+    }
+}
+
+impl<T: IntoLox> NativeResult for Result<T, RuntimeError> {
+    fn into_result(self) -> Result<IResult, RuntimeError> {
+        self.map(T::into_lox)
+    }
+}
+
+// implemented for closures and functions taking up to 5 arguments that convert
+// from Lox values; `Args` is the tuple of argument types
+pub trait IntoNative<Args> {
+    fn into_native(self) -> LoxCallable;
+}
+
+// wraps a rust function into a callable whose arity is its number of arguments
+pub fn native<Args, F: IntoNative<Args>>(function: F) -> LoxCallable {
+    function.into_native()
+}
+
+macro_rules! impl_into_native {
+    ($arity:expr $(, $arg:ident)*) => {
+        impl<F, R, $($arg,)*> IntoNative<($($arg,)*)> for F
+        where
+            F: Fn($($arg),*) -> R + 'static,
+            R: NativeResult,
+            $($arg: FromLox,)*
+        {
+            #[allow(non_snake_case, unused_mut, unused_variables)]
+            fn into_native(self) -> LoxCallable {
+                LoxCallable::new_native($arity, move |args| {
+                    let mut args = args.into_iter().enumerate();
+                    $(
+                        let (index, value) = args.next().expect("arity is checked by the caller");
+                        let $arg = $arg::from_lox(value).map_err(|err| {
+                            RuntimeError::native(
+                                err.kind,
+                                format!("argument {}: {}", index + 1, err.message).as_str(),
+                            )
+                        })?;
+                    )*
+                    self($($arg),*).into_result()
+                })
+            }
+        }
+    };
+}
+
+impl_into_native!(0);
+impl_into_native!(1, A);
+impl_into_native!(2, A, B);
+impl_into_native!(3, A, B, C);
+impl_into_native!(4, A, B, C, D);
+impl_into_native!(5, A, B, C, D, E);
@@ -1,60 +1,99 @@
 use std::{collections::HashMap, rc::Rc};
 
-use rustcore::Shared;
+use rustcore::{Gc, Trace, Tracer};
 
-use crate::result::IResult;
+use crate::{error::RuntimeErrorKind, result::IResult};
 
-pub type EnvironmentRef = Shared<Environment>;
+pub type EnvironmentRef = Gc<Environment>;
 
+// A local scope. Variables are stored in declaration order and accessed by the
+// (depth, slot) the resolver computed for them, so no names are kept around.
 pub struct Environment {
     parent: Option<EnvironmentRef>,
-    bindings: HashMap<String, Rc<IResult>>,
+    // a declared variable is bound to None until a value is assigned to it
+    slots: Vec<Option<Rc<IResult>>>,
 }
 
 impl Environment {
+    // the outermost scope; declarations made in it go to the globals instead
     pub fn new() -> EnvironmentRef {
-        Shared::new(Environment {
+        Gc::new(Environment {
             parent: None,
-            bindings: HashMap::new(),
+            slots: Vec::new(),
         })
     }
 
     pub fn new_with_parent(parent: EnvironmentRef) -> EnvironmentRef {
-        Shared::new(Environment {
+        Gc::new(Environment {
             parent: Some(parent),
-            bindings: HashMap::new(),
+            slots: Vec::new(),
         })
     }
 
-    pub fn is_binded(&self, name: &str) -> bool {
-        self.bindings.contains_key(name)
-            || (self.parent.is_some() && self.parent.as_ref().unwrap().is_binded(name))
+    pub fn is_global_scope(&self) -> bool {
+        self.parent.is_none()
     }
 
-    pub fn get(&self, name: &str) -> Option<Rc<IResult>> {
-        self.bindings
-            .get(name)
-            .cloned()
-            .or_else(|| self.parent.as_ref().and_then(|p| p.get(name)))
+    pub fn parent(&self) -> Option<EnvironmentRef> {
+        self.parent.clone()
     }
 
-    pub fn declare_and_init(&mut self, name: &str, value: IResult) {
-        self.bindings.insert(name.to_string(), Rc::new(value));
+    // the environment `depth` scopes up the parent chain
+    fn ancestor(&self, depth: usize) -> &Environment {
+        let mut environment = self;
+        for _ in 0..depth {
+            environment = environment
+                .parent
+                .as_ref()
+                .expect("resolved depth is within the environment chain");
+        }
+        environment
     }
 
-    pub fn declare(&mut self, name: &str) {
-        self.bindings
-            .insert(name.to_string(), Rc::new(IResult::None));
+    pub fn get_at(&self, depth: usize, slot: usize) -> Result<Rc<IResult>, RuntimeErrorKind> {
+        match self.ancestor(depth).slots.get(slot) {
+            Some(Some(value)) => Ok(value.clone()),
+            Some(None) => Err(RuntimeErrorKind::UninitializedVariable),
+            None => Err(RuntimeErrorKind::UndefinedVariable),
+        }
     }
 
-    pub fn assign(&mut self, name: &str, value: IResult) -> bool {
-        if self.bindings.contains_key(name) {
-            self.bindings.insert(name.to_string(), Rc::new(value));
-            true
-        } else {
-            self.parent
+    pub fn assign_at(&mut self, depth: usize, slot: usize, value: IResult) -> bool {
+        if depth > 0 {
+            return self
+                .parent
                 .as_ref()
-                .map_or(false, |p| p.borrow_mut().assign(name, value))
+                .is_some_and(|p| p.borrow_mut().assign_at(depth - 1, slot, value));
+        }
+        match self.slots.get_mut(slot) {
+            Some(binding) => {
+                *binding = Some(Rc::new(value));
+                true
+            }
+            None => false,
+        }
+    }
+
+    // binds the next slot
+    pub fn define(&mut self, value: IResult) {
+        self.slots.push(Some(Rc::new(value)));
+    }
+
+    pub fn declare(&mut self) {
+        self.slots.push(None);
+    }
+}
+
+impl Trace for Environment {
+    fn trace(&self, tracer: &mut Tracer) {
+        if let Some(parent) = &self.parent {
+            tracer.edge(parent);
+        }
+        for value in self.slots.iter().flatten() {
+            // a value also held by the interpreter keeps what it references alive
+            if Rc::strong_count(value) == 1 {
+                value.trace(tracer);
+            }
         }
     }
 }
@@ -62,6 +101,41 @@ impl Environment {
 impl Drop for Environment {
     fn drop(&mut self) {
         //println!("dropping out of scope");
-        self.bindings.clear();
+        self.slots.clear();
+    }
+}
+
+// Variables declared at the top level. The resolver leaves references to them
+// unbound, so they are looked up by name.
+#[derive(Default)]
+pub struct Globals {
+    bindings: HashMap<String, Option<Rc<IResult>>>,
+}
+
+impl Globals {
+    pub fn get(&self, name: &str) -> Result<Rc<IResult>, RuntimeErrorKind> {
+        match self.bindings.get(name) {
+            Some(Some(value)) => Ok(value.clone()),
+            Some(None) => Err(RuntimeErrorKind::UninitializedVariable),
+            None => Err(RuntimeErrorKind::UndefinedVariable),
+        }
+    }
+
+    pub fn define(&mut self, name: &str, value: IResult) {
+        self.bindings.insert(name.to_string(), Some(Rc::new(value)));
+    }
+
+    pub fn declare(&mut self, name: &str) {
+        self.bindings.insert(name.to_string(), None);
+    }
+
+    pub fn assign(&mut self, name: &str, value: IResult) -> bool {
+        match self.bindings.get_mut(name) {
+            Some(binding) => {
+                *binding = Some(Rc::new(value));
+                true
+            }
+            None => false,
+        }
     }
 }
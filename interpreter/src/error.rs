@@ -0,0 +1,143 @@
+use std::fmt::Display;
+
+use std::rc::Rc;
+
+use crate::result::IResult;
+use scanner::{
+    diagnostics::Diagnostic,
+    tokens::{new_token, Span, TokenRef, TokenType},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RuntimeErrorKind {
+    UndefinedVariable,
+    UninitializedVariable,
+    InvalidOperand,
+    NotCallable,
+    ArityMismatch,
+    UndefinedProperty,
+    NotAnInstance,
+    InvalidSuperclass,
+    Io,
+    // more calls running at once than `FRAMES_MAX`
+    StackOverflow,
+    // a limit set by the host, see `limits::Limits`
+    ResourceLimitExceeded,
+    // stopped through an `interrupt::InterruptHandle`
+    Interrupted,
+    // a return, break or continue with nothing to leave, in code the resolver didn't check
+    InvalidControlFlow,
+    // the script called exit with this status
+    Exit(i32),
+    // compiled code the vm can't run, from a file that passed the checks made on loading
+    InvalidBytecode,
+}
+
+// a function that was running when an error was raised, and the line it was at
+#[derive(Clone, Debug, PartialEq)]
+pub struct CallFrame {
+    pub function: String,
+    pub line: u32,
+}
+
+#[derive(Clone, Debug)]
+pub struct RuntimeError {
+    pub token: TokenRef,
+    pub kind: RuntimeErrorKind,
+    pub message: String,
+    // the part of the source the error points at; the token's span unless narrowed down
+    pub span: Span,
+    // the calls that led to the error, innermost first; empty until the error
+    // leaves the function it was raised in
+    pub backtrace: Vec<CallFrame>,
+}
+
+impl RuntimeError {
+    pub fn new(token: TokenRef, kind: RuntimeErrorKind, message: &str) -> Self {
+        Self {
+            span: token.span,
+            token,
+            kind,
+            message: message.to_string(),
+            backtrace: Vec::new(),
+        }
+    }
+
+    // an error raised by a native function, which doesn't know where it was called
+    // from; the interpreter points it at the call
+    pub fn native(kind: RuntimeErrorKind, message: &str) -> Self {
+        Self::new(Rc::new(new_token(TokenType::Eof)), kind, message)
+    }
+
+    // locates errors that don't point at the source yet
+    pub fn or_at(mut self, token: &TokenRef, span: Span) -> Self {
+        // spans of real tokens start at line 1
+        if self.span.line == 0 {
+            self.token = token.clone();
+            self.span = span;
+        }
+        self
+    }
+
+    pub fn with_span(mut self, span: Span) -> Self {
+        self.span = span;
+        self
+    }
+
+    pub fn line(&self) -> u32 {
+        self.span.line
+    }
+
+    pub fn diagnostic(&self) -> Diagnostic {
+        let diagnostic = Diagnostic::new(&self.message, self.span);
+        // errors raised by the script itself are located well enough by their span
+        if self.backtrace.len() < 2 {
+            return diagnostic;
+        }
+        diagnostic.with_backtrace(
+            self.backtrace
+                .iter()
+                .map(|frame| (frame.function.clone(), frame.line))
+                .collect(),
+        )
+    }
+}
+
+impl Display for RuntimeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}\n[line {}]", self.message, self.line())
+    }
+}
+
+impl std::error::Error for RuntimeError {}
+
+// why a statement stopped before reaching its end: a runtime error, or a jump out
+// to the enclosing function or loop. Unwinds the interpreter until it is handled.
+#[derive(Clone, Debug)]
+pub enum Unwind {
+    Error(RuntimeError),
+    Return(IResult),
+    Break,
+    Continue,
+}
+
+impl Unwind {
+    // the error for an unwind that reached the function or script it was raised in.
+    // The resolver rejects code jumping out of those, but hosts can run
+    // declarations that weren't resolved.
+    pub fn into_error(self) -> RuntimeError {
+        let message = match self {
+            Unwind::Error(err) => return err,
+            Unwind::Return(_) => "can't return from top-level code",
+            Unwind::Break => "break outside loop",
+            Unwind::Continue => "continue outside loop",
+        };
+        RuntimeError::native(RuntimeErrorKind::InvalidControlFlow, message)
+    }
+}
+
+impl From<RuntimeError> for Unwind {
+    fn from(err: RuntimeError) -> Self {
+        Unwind::Error(err)
+    }
+}
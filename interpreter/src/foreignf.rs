@@ -1,18 +1,124 @@
-use crate::{callable::LoxCallable, result::IResult};
-
-struct ForeignFunctions {}
-impl ForeignFunctions {
-    fn clock() -> LoxCallable {
-        LoxCallable {
-            arity: 0,
-            call: Box::new(|_, _| {
-                IResult::Number(
-                    std::time::SystemTime::now()
-                        .duration_since(std::time::UNIX_EPOCH)
-                        .unwrap()
-                        .as_secs() as f64,
-                )
-            }),
+use std::{
+    io::{stdin, BufRead},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    callable::LoxCallable,
+    convert::{native, IntoNative},
+    error::{RuntimeError, RuntimeErrorKind},
+    result::IResult,
+};
+
+// Functions implemented in Rust that scripts can call like any other function.
+// `Interpreter::new_with_out` installs the builtins as globals; embedding hosts
+// can start from `NativeRegistry::default()`, register their own functions and
+// hand the registry to `Interpreter::new_with_registry`.
+pub struct NativeRegistry {
+    functions: Vec<(String, LoxCallable)>,
+}
+
+impl Default for NativeRegistry {
+    fn default() -> Self {
+        let mut registry = NativeRegistry::empty();
+        registry
+            .register_fn("clock", clock)
+            .register_fn("str", |value: IResult| value.to_string())
+            .register_fn("num", num)
+            .register_fn("type", |value: IResult| value.type_name())
+            .register_fn("len", len)
+            .register_fn("input", input)
+            .register_fn("exit", exit);
+        registry
+    }
+}
+
+impl NativeRegistry {
+    // a registry without the builtins
+    pub fn empty() -> Self {
+        Self {
+            functions: Vec::new(),
         }
     }
+
+    // registering a name twice replaces the earlier function
+    pub fn register(&mut self, name: &str, callable: LoxCallable) -> &mut Self {
+        self.functions.retain(|(existing, _)| existing != name);
+        self.functions.push((name.to_string(), callable));
+        self
+    }
+
+    // registers a rust function taking and returning values that convert to and
+    // from Lox ones; see `convert`
+    pub fn register_fn<Args, F: IntoNative<Args>>(&mut self, name: &str, function: F) -> &mut Self {
+        self.register(name, native(function))
+    }
+
+    pub fn into_functions(self) -> impl Iterator<Item = (String, LoxCallable)> {
+        self.functions.into_iter()
+    }
+}
+
+// seconds since the unix epoch, with sub-second precision
+fn clock() -> Result<f64, RuntimeError> {
+    let elapsed = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(|err| RuntimeError::native(RuntimeErrorKind::Io, &err.to_string()))?;
+    Ok(elapsed.as_secs_f64())
+}
+
+fn num(value: IResult) -> Result<f64, RuntimeError> {
+    match &value {
+        IResult::Number(n) => Ok(*n),
+        IResult::String(s) => s.trim().parse::<f64>().map_err(|_| {
+            RuntimeError::native(
+                RuntimeErrorKind::InvalidOperand,
+                format!("can't convert \"{}\" to a number", s).as_str(),
+            )
+        }),
+        other => Err(RuntimeError::native(
+            RuntimeErrorKind::InvalidOperand,
+            format!("can't convert {} to a number", other.type_name()).as_str(),
+        )),
+    }
+}
+
+fn len(value: IResult) -> Result<f64, RuntimeError> {
+    match &value {
+        IResult::String(s) => Ok(s.chars().count() as f64),
+        IResult::List(items) => Ok(items.len() as f64),
+        other => Err(RuntimeError::native(
+            RuntimeErrorKind::InvalidOperand,
+            format!(
+                "len expects a string or a list but got {}",
+                other.type_name()
+            )
+            .as_str(),
+        )),
+    }
+}
+
+// a line read from stdin without its line ending, or nil at the end of the input
+fn input() -> Result<Option<String>, RuntimeError> {
+    let mut line = String::new();
+    match stdin().lock().read_line(&mut line) {
+        Ok(0) => Ok(None),
+        Ok(_) => {
+            let trimmed = line.trim_end_matches(['\n', '\r']).len();
+            line.truncate(trimmed);
+            Ok(Some(line))
+        }
+        Err(err) => Err(RuntimeError::native(
+            RuntimeErrorKind::Io,
+            format!("failed to read input: {}", err).as_str(),
+        )),
+    }
+}
+
+// stops the script; ending the process is up to the host, see RuntimeErrorKind::Exit
+fn exit(code: f64) -> Result<(), RuntimeError> {
+    Err(RuntimeError::native(
+        RuntimeErrorKind::Exit(code as i32),
+        "script called exit",
+    ))
 }
@@ -1,29 +1,57 @@
-mod callable;
+pub mod callable;
+mod class;
+pub mod convert;
 mod environment;
-mod foreignf;
-mod result;
+pub mod error;
+pub mod foreignf;
+pub mod interrupt;
+pub mod limits;
+pub mod result;
 
 use std::io::{stdout, Stdout, Write};
 
-use std::panic::{self, AssertUnwindSafe};
 use std::rc::Rc;
 
 use callable::{EnvironmentAware, LoxCallable, VisitorEnvironmentAware};
-use environment::{Environment, EnvironmentRef};
+use class::{LoxClass, LoxInstance};
+use environment::{Environment, EnvironmentRef, Globals};
+use error::{CallFrame, RuntimeError, RuntimeErrorKind, Unwind};
+use foreignf::NativeRegistry;
+use interrupt::InterruptHandle;
+use limits::{Budget, Limits, OBJECT_SIZE};
 use parser::ast::{self, Binary, FunDecl, Grouping, Literal, Unary};
 use parser::utils::Visitor;
 
-use rustcore::Shared;
-use scanner::tokens::TokenType;
+use rustcore::{Gc, Shared};
+use scanner::tokens::{Span, TokenRef, TokenType};
 
 use result::IResult;
-use result::IResult::{Bool, None, Number, String};
+use result::IResult::{Bool, Nil, None, Number, String};
 
-static INTERPRETER_ERR_TAG: &str = "INTERPRETER_ERROR:";
+type EvalResult = Result<IResult, Unwind>;
+
+// Calls running at once, the script included, past which a run fails with a stack
+// overflow; the vm uses the same bound so that both report the same error. A call
+// of the tree-walker takes about 8KB of native stack in unoptimised builds, so this
+// many fit in the 2MB Rust gives new threads.
+pub const FRAMES_MAX: usize = 128;
+
+// Native stack the tree-walker may use from where a run started. It recurses on it
+// for expressions as well as calls, so calls nested in deep expressions fail with a
+// stack overflow here before FRAMES_MAX is reached. Hosts running scripts on threads
+// with less than 2MB of stack need to keep them shallower with `max_call_depth`.
+const NATIVE_STACK_MAX: usize = 1536 * 1024;
 
 pub struct Interpreter<T: Write> {
     environment: EnvironmentRef,
+    // variables declared at the top level; the resolver leaves references to them unbound
+    globals: Shared<Globals>,
     ostream: Shared<T>,
+    // the functions being run, innermost last, each with the line it is at
+    frames: Vec<CallFrame>,
+    budget: Budget,
+    // address on the native stack of where the running script started
+    stack_base: usize,
 }
 
 impl<T: Write> EnvironmentAware for Interpreter<T> {
@@ -38,56 +66,39 @@ impl<T: Write> EnvironmentAware for Interpreter<T> {
 
 impl<T: Write> VisitorEnvironmentAware for Interpreter<T> {}
 
-impl<T: Write> Visitor<IResult> for Interpreter<T> {
-    fn visit_var_decl(&mut self, decl: &ast::VarDecl) -> IResult {
-        if decl.rhs.is_none() {
-            self.environment
-                .borrow_mut()
-                .declare(&decl.identifier.lexeme);
-        } else {
-            let rhs_result = self.visit_expression(decl.rhs.as_ref().unwrap().as_ref());
-            self.environment
-                .borrow_mut()
-                .declare_and_init(&decl.identifier.lexeme, rhs_result);
-        }
+impl<T: Write> Visitor<EvalResult> for Interpreter<T> {
+    fn visit_var_decl(&mut self, decl: &ast::VarDecl) -> EvalResult {
+        let value = match &decl.rhs {
+            Some(rhs) => Some(self.visit_expression(rhs.as_ref())?),
+            Option::None => Option::None,
+        };
+        self.define(&decl.identifier.lexeme, value);
 
-        None
+        Ok(None)
     }
 
-    fn visit_print_stmt(&mut self, stmt: &ast::PrintStmt) -> IResult {
-        let value = self.visit_expression(&stmt.value);
-        //self.ostream.by_ref()
-        //let mut ss = self.ostream;
-        match writeln!(self.ostream.borrow_mut(), "{}", value) {
-            Ok(_) => {}
-            Err(err) => self.error(
-                &TokenType::Print,
+    fn visit_print_stmt(&mut self, stmt: &ast::PrintStmt) -> EvalResult {
+        let value = self.visit_expression(&stmt.value)?;
+        if let Err(err) = writeln!(self.ostream.borrow_mut(), "{}", value) {
+            return self.error(
+                &stmt.keyword,
+                RuntimeErrorKind::Io,
                 format!("failed to write to output stream: {:?}", err).as_str(),
-            ),
+            );
         }
-        None
+        Ok(None)
     }
 
-    fn visit_block_stmt(&mut self, stmt: &ast::BlockStmt) -> IResult {
+    fn visit_block_stmt(&mut self, stmt: &ast::BlockStmt) -> EvalResult {
         let parent: EnvironmentRef = self.environment.clone();
         self.environment = Environment::new_with_parent(parent.clone());
-        let mut result = None;
+        let mut result = Ok(None);
 
+        // return and break leave the block like errors do; the scope is closed either way
         for decl in stmt.declarations.iter() {
-            let decl_result = self.visit_declaration(decl.clone());
-            match decl_result {
-                IResult::Break => {
-                    result = IResult::Break;
-                    break;
-                }
-                IResult::Return(val) => {
-                    result = val.as_ref().clone();
-                    break;
-                }
-
-                _ => {
-                    continue;
-                }
+            if let Err(unwind) = self.visit_declaration(decl.clone()) {
+                result = Err(unwind);
+                break;
             }
         }
 
@@ -96,73 +107,84 @@ impl<T: Write> Visitor<IResult> for Interpreter<T> {
         result
     }
 
-    fn visit_if_stmt(&mut self, stmt: &ast::IfStmt) -> IResult {
-        let condition_val = self.visit_expression(&stmt.condition);
-        if let Bool(condition) = condition_val {
-            return if condition {
-                self.visit_statement(&stmt.then_b)
-            } else if let Some(else_b) = &stmt.else_b {
-                self.visit_statement(else_b)
-            } else {
-                None
-            };
+    fn visit_if_stmt(&mut self, stmt: &ast::IfStmt) -> EvalResult {
+        if self.visit_expression(&stmt.condition)?.is_truthy() {
+            self.visit_statement(&stmt.then_b)
+        } else if let Some(else_b) = &stmt.else_b {
+            self.visit_statement(else_b)
         } else {
-            self.error(&TokenType::If, "condition expression should return boolean")
+            Ok(None)
         }
     }
 
-    fn visit_while_stmt(&mut self, stmt: &ast::WhileStmt) -> IResult {
-        let condition_val = self.visit_expression(&stmt.condition);
-        if let Bool(condition) = condition_val {
-            if condition {
-                if self.visit_block_stmt(&stmt.body) != IResult::Break {
-                    self.visit_while_stmt(stmt);
-                }
+    fn visit_while_stmt(&mut self, stmt: &ast::WhileStmt) -> EvalResult {
+        // iterate instead of recursing so that long running loops don't grow the stack
+        while self.visit_expression(&stmt.condition)?.is_truthy() {
+            match self.visit_block_stmt(&stmt.body) {
+                Err(Unwind::Break) => break,
+                Ok(_) | Err(Unwind::Continue) => {}
+                // errors and returns carry on to the enclosing function
+                Err(unwind) => return Err(unwind),
             }
-        } else {
-            self.error(
-                &TokenType::While,
-                "condition expression should return boolean",
-            )
+            if let Some(increment) = &stmt.increment {
+                self.visit_expression(increment)?;
+            }
+            self.budget
+                .step()
+                .map_err(|err| err.or_at(&stmt.keyword, stmt.keyword.span))?;
         }
 
-        None
+        Ok(None)
+    }
+
+    fn visit_break_stmt(&mut self, _stmt: &ast::BreakStmt) -> EvalResult {
+        // the resolver rejects breaks outside of loops
+        Err(Unwind::Break)
     }
 
-    fn visit_break_stmt(&mut self, _stmt: &ast::BreakStmt) -> IResult {
-        IResult::Break
+    fn visit_continue_stmt(&mut self, _stmt: &ast::ContinueStmt) -> EvalResult {
+        // the resolver rejects continues outside of loops
+        Err(Unwind::Continue)
     }
 
-    fn visit_literal(&mut self, lit: &Literal) -> IResult {
+    fn visit_literal(&mut self, lit: &Literal) -> EvalResult {
         match &lit.value.ttype {
-            TokenType::String(contents) => String(Rc::new(contents.to_string())),
-            TokenType::Number(value) => Number(*value),
-            TokenType::True => Bool(true),
-            TokenType::False => Bool(false),
+            TokenType::String(contents) => Ok(String(Rc::new(contents.to_string()))),
+            TokenType::Number(value) => Ok(Number(*value)),
+            TokenType::True => Ok(Bool(true)),
+            TokenType::False => Ok(Bool(false)),
+            TokenType::Nil => Ok(Nil),
             TokenType::Identifier => {
                 let var = &lit.value.lexeme;
-                if let Some(value) = self.environment.get(var) {
-                    if IResult::None.eq(value.as_ref()) {
-                        self.error(&lit.value.ttype, "variable is not initialized");
+                let value = match lit.binding.get() {
+                    Some(binding) => self.environment.get_at(binding.depth, binding.slot),
+                    Option::None => self.globals.get(var),
+                };
+                match value {
+                    Ok(value) => Ok(value.as_ref().clone()),
+                    Err(kind @ RuntimeErrorKind::UninitializedVariable) => {
+                        self.error(&lit.value, kind, "variable is not initialized")
                     }
-
-                    value.as_ref().clone()
-                } else {
-                    self.error(
-                        &lit.value.ttype,
+                    Err(kind) => self.error(
+                        &lit.value,
+                        kind,
                         format!("variable {} not in scope", var.as_str()).as_str(),
-                    )
+                    ),
                 }
             }
-            tkn => self.error(tkn, "invalid token found; expected literal"),
+            _ => self.error(
+                &lit.value,
+                RuntimeErrorKind::InvalidOperand,
+                "invalid token found; expected literal",
+            ),
         }
     }
 
-    fn visit_grouping(&mut self, grp: &Grouping) -> IResult {
+    fn visit_grouping(&mut self, grp: &Grouping) -> EvalResult {
         self.visit_expression(&grp.expr)
     }
 
-    fn visit_unary(&mut self, unr: &Unary) -> IResult {
+    fn visit_unary(&mut self, unr: &Unary) -> EvalResult {
         match &unr.operator.ttype {
             TokenType::Minus | TokenType::Plus => {
                 let minus = if unr.operator.ttype == TokenType::Minus {
@@ -170,239 +192,351 @@ impl<T: Write> Visitor<IResult> for Interpreter<T> {
                 } else {
                     1.0
                 };
-                if let Number(value) = self.visit_expression(&unr.expr) {
-                    Number(minus * value)
+                if let Number(value) = self.visit_expression(&unr.expr)? {
+                    Ok(Number(minus * value))
                 } else {
                     self.error(
-                        &unr.operator.ttype,
+                        &unr.operator,
+                        RuntimeErrorKind::InvalidOperand,
                         "invalid operand for plus/minus operator",
-                    );
-                }
-            }
-            TokenType::Bang => {
-                if let Bool(value) = self.visit_expression(&unr.expr) {
-                    Bool(!value)
-                } else {
-                    self.error(&unr.operator.ttype, "invalid operand for bang operator");
+                    )
                 }
             }
-            tkn => self.error(tkn, "invalid token found; expected unary operator"),
+            TokenType::Bang => Ok(Bool(!self.visit_expression(&unr.expr)?.is_truthy())),
+            _ => self.error(
+                &unr.operator,
+                RuntimeErrorKind::InvalidOperand,
+                "invalid token found; expected unary operator",
+            ),
         }
     }
 
-    fn visit_binary(&mut self, bin: &Binary) -> IResult {
-        let leftv = self.visit_expression(&bin.left);
-        let rightv = self.visit_expression(&bin.right);
-        match &bin.operator.ttype {
+    fn visit_binary(&mut self, bin: &Binary) -> EvalResult {
+        let leftv = self.visit_expression(&bin.left)?;
+        let rightv = self.visit_expression(&bin.right)?;
+        let (errmsg, kind) = match &bin.operator.ttype {
             TokenType::Plus => {
-                if let Number(left) = leftv {
-                    if let Number(right) = rightv {
-                        return Number(left + right);
-                    }
+                if let (Number(left), Number(right)) = (&leftv, &rightv) {
+                    return Ok(Number(left + right));
                 }
-                if let String(left) = leftv {
-                    if let String(right) = rightv {
-                        return String(Rc::new(format!("{}{}", left, right)));
-                    }
+                if let (String(left), String(right)) = (&leftv, &rightv) {
+                    let bytes = left.len() + right.len();
+                    self.allocate(bytes, &bin.operator, bin.operator.span)?;
+                    return Ok(String(Rc::new(format!("{}{}", left, right))));
                 }
-                self.error(&bin.operator.ttype, "invalid operands for plus operator");
+                (
+                    "invalid operands for plus operator",
+                    RuntimeErrorKind::InvalidOperand,
+                )
             }
             TokenType::Minus => {
-                if let Number(left) = leftv {
-                    if let Number(right) = rightv {
-                        return Number(left - right);
-                    }
+                if let (Number(left), Number(right)) = (&leftv, &rightv) {
+                    return Ok(Number(left - right));
                 }
-                self.error(&bin.operator.ttype, "invalid operands for minus operator");
+                (
+                    "invalid operands for minus operator",
+                    RuntimeErrorKind::InvalidOperand,
+                )
             }
             TokenType::Star => {
-                if let Number(left) = leftv {
-                    if let Number(right) = rightv {
-                        return Number(left * right);
-                    }
+                if let (Number(left), Number(right)) = (&leftv, &rightv) {
+                    return Ok(Number(left * right));
                 }
-                self.error(&bin.operator.ttype, "invalid operands for star operator");
+                (
+                    "invalid operands for star operator",
+                    RuntimeErrorKind::InvalidOperand,
+                )
             }
             TokenType::Slash => {
-                if let Number(left) = leftv {
-                    if let Number(right) = rightv {
-                        return Number(left / right);
-                    }
+                if let (Number(left), Number(right)) = (&leftv, &rightv) {
+                    return Ok(Number(left / right));
                 }
-                self.error(&bin.operator.ttype, "invalid operands for slash operator");
+                (
+                    "invalid operands for slash operator",
+                    RuntimeErrorKind::InvalidOperand,
+                )
             }
             TokenType::Greater => {
-                if let Number(left) = leftv {
-                    if let Number(right) = rightv {
-                        return Bool(left > right);
-                    }
+                if let (Number(left), Number(right)) = (&leftv, &rightv) {
+                    return Ok(Bool(left > right));
                 }
-                self.error(&bin.operator.ttype, "invalid operands for greater operator");
+                (
+                    "invalid operands for greater operator",
+                    RuntimeErrorKind::InvalidOperand,
+                )
             }
             TokenType::GreaterEqual => {
-                if let Number(left) = leftv {
-                    if let Number(right) = rightv {
-                        return Bool(left >= right);
-                    }
+                if let (Number(left), Number(right)) = (&leftv, &rightv) {
+                    return Ok(Bool(left >= right));
                 }
-                self.error(&bin.operator.ttype, "invalid operands for greater-equal")
+                (
+                    "invalid operands for greater-equal",
+                    RuntimeErrorKind::InvalidOperand,
+                )
             }
-            TokenType::LeftParen => todo!(),
-            TokenType::RightParen => todo!(),
-            TokenType::LeftBrace => todo!(),
-            TokenType::RightBrace => todo!(),
-            TokenType::Comma => todo!(),
-            TokenType::Dot => todo!(),
-            TokenType::Semicolon => todo!(),
-            TokenType::Bang => todo!(),
-            TokenType::BangEqual => Bool(leftv != rightv),
-            TokenType::Equal => todo!(),
-            TokenType::EqualEqual => Bool(leftv == rightv),
             TokenType::Less => {
-                if let Number(left) = leftv {
-                    if let Number(right) = rightv {
-                        return Bool(left < right);
-                    }
+                if let (Number(left), Number(right)) = (&leftv, &rightv) {
+                    return Ok(Bool(left < right));
                 }
-                self.error(&bin.operator.ttype, "invalid operands for less")
+                (
+                    "invalid operands for less",
+                    RuntimeErrorKind::InvalidOperand,
+                )
             }
             TokenType::LessEqual => {
-                if let Number(left) = leftv {
-                    if let Number(right) = rightv {
-                        return Bool(left <= right);
-                    }
+                if let (Number(left), Number(right)) = (&leftv, &rightv) {
+                    return Ok(Bool(left <= right));
                 }
-                self.error(&bin.operator.ttype, "invalid operands for less-equal")
+                (
+                    "invalid operands for less-equal",
+                    RuntimeErrorKind::InvalidOperand,
+                )
             }
-            TokenType::Identifier => todo!(),
-            TokenType::String(_) => todo!(),
-            TokenType::Number(_) => todo!(),
-            TokenType::And => todo!(),
-            TokenType::Class => todo!(),
-            TokenType::Else => todo!(),
-            TokenType::False => todo!(),
-            TokenType::Fun => todo!(),
-            TokenType::For => todo!(),
-            TokenType::If => todo!(),
-            TokenType::Nil => todo!(),
-            TokenType::Or => todo!(),
-            TokenType::Print => todo!(),
-            TokenType::Return => todo!(),
-            TokenType::Super => todo!(),
-            TokenType::This => todo!(),
-            TokenType::True => todo!(),
-            TokenType::Var => todo!(),
-            TokenType::While => todo!(),
-            TokenType::Eof => todo!(),
-            TokenType::Break => todo!(),
-        }
+            TokenType::BangEqual => return Ok(Bool(leftv != rightv)),
+            TokenType::EqualEqual => return Ok(Bool(leftv == rightv)),
+            _ => (
+                "invalid token found; expected binary operator",
+                RuntimeErrorKind::InvalidOperand,
+            ),
+        };
+
+        self.error(&bin.operator, kind, errmsg)
     }
 
-    fn visit_logical(&mut self, logic: &ast::Logical) -> IResult {
-        let leftv = self.visit_expression(&logic.left);
+    fn visit_logical(&mut self, logic: &ast::Logical) -> EvalResult {
+        let leftv = self.visit_expression(&logic.left)?;
 
-        if let Bool(leftvb) = leftv {
-            if leftvb == (logic.operator.ttype == TokenType::Or) {
-                // short circuit
-                return Bool(leftvb);
-            } else {
-                let rightv = self.visit_expression(&logic.right);
-                if let Bool(rightvb) = rightv {
-                    return Bool(rightvb);
-                } else {
-                    self.error(&TokenType::Or, "condition expression should return boolean")
-                }
-            }
+        // the operand that decides the outcome is the result, not a bool
+        if leftv.is_truthy() == (logic.operator.ttype == TokenType::Or) {
+            // short circuit
+            Ok(leftv)
         } else {
-            self.error(
-                &logic.operator.ttype,
-                "invalid operands for logical operator",
-            );
+            self.visit_expression(&logic.right)
         }
     }
 
-    fn visit_assign(&mut self, assign: &ast::Assign) -> IResult {
+    fn visit_assign(&mut self, assign: &ast::Assign) -> EvalResult {
         let identifier = assign.identifier.lexeme.as_str();
-        if self.environment.is_binded(identifier) {
-            let rhs = self.visit_expression(&assign.value);
-            self.environment.borrow_mut().assign(identifier, rhs);
-            None
+        let rhs = self.visit_expression(&assign.value)?;
+        let assigned = match assign.binding.get() {
+            Some(binding) => {
+                self.environment
+                    .borrow_mut()
+                    .assign_at(binding.depth, binding.slot, rhs)
+            }
+            Option::None => self.globals.borrow_mut().assign(identifier, rhs),
+        };
+        if assigned {
+            Ok(None)
         } else {
             self.error(
-                &assign.identifier.ttype,
+                &assign.identifier,
+                RuntimeErrorKind::UndefinedVariable,
                 format!("{} is not binded", identifier).as_str(),
-            );
+            )
         }
     }
 
-    fn visit_call(&mut self, call: &ast::Call) -> IResult {
-        let callee = self.visit_expression(&call.callee);
-        if let IResult::Callable(arg0) = callee {
-            let arguments = call
-                .arguments
-                .iter()
-                .map(|a| self.visit_expression(a))
-                .collect::<Vec<IResult>>();
-            if arguments.len() != arg0.arity {
-                self.error(
-                    &TokenType::Identifier,
-                    format!(
-                        "expected {} arguments but got {}",
-                        arg0.arity,
-                        arguments.len()
-                    )
-                    .as_str(),
-                );
-            }
-            (arg0.borrow_mut().call)(self as &mut dyn VisitorEnvironmentAware, arguments)
-        } else {
-            self.error(
-                &TokenType::Identifier,
+    fn visit_call(&mut self, call: &ast::Call) -> EvalResult {
+        let callee = self.visit_expression(&call.callee)?;
+        let Some(arity) = arity_of(&callee) else {
+            return Err(RuntimeError::new(
+                call.paren.clone(),
+                RuntimeErrorKind::NotCallable,
                 format!("{} is not callable", callee).as_str(),
+            )
+            .with_span(call.callee.span())
+            .into());
+        };
+
+        let arguments = call
+            .arguments
+            .iter()
+            .map(|a| self.visit_expression(a))
+            .collect::<Result<Vec<IResult>, Unwind>>()?;
+        if arguments.len() != arity {
+            return self.error(
+                &call.paren,
+                RuntimeErrorKind::ArityMismatch,
+                format!("expected {} arguments but got {}", arity, arguments.len()).as_str(),
             );
         }
+
+        if let Some(frame) = self.frames.last_mut() {
+            frame.line = call.span.line;
+        }
+        Ok(self
+            .invoke(callee, arguments)
+            .map_err(|err| err.or_at(&call.paren, call.span))?)
     }
 
-    fn visit_fun_decl(&mut self, decl: Rc<FunDecl>) -> IResult {
+    fn visit_fun_decl(&mut self, decl: Rc<FunDecl>) -> EvalResult {
+        self.allocate(OBJECT_SIZE, &decl.identifier, decl.span)?;
         // variables and function names share the same namespace
-        let identifier = decl.identifier.lexeme.as_str();
-        let decl = decl.clone();
-        let declaring_env = self.environment.clone();
-        let callable = LoxCallable {
-            arity: decl.params.len(),
-            call: Box::new(move |visitor, params: Vec<IResult>| {
-                // bind the variables
-                // call the function body
-                // return the result
-                let original_env = visitor.get_environment();
-                let parent = declaring_env.clone();
-                visitor.set_environment(Environment::new_with_parent(parent.clone()));
-
-                let mut viter = params.iter();
-                for param in decl.params.iter() {
-                    visitor
-                        .get_environment()
-                        .declare_and_init(param.lexeme.as_str(), viter.next().unwrap().clone())
+        let callable = LoxCallable::new_function(decl.clone(), self.environment.clone(), false);
+        self.define(
+            &decl.identifier.lexeme,
+            Some(IResult::Callable(Gc::new(callable))),
+        );
+
+        Ok(None)
+    }
+
+    fn visit_class_decl(&mut self, decl: &ast::ClassDecl) -> EvalResult {
+        let mut closure = self.environment.clone();
+        let mut superclass = Option::None;
+        if let Some(expr) = &decl.superclass {
+            match self.visit_expression(expr)? {
+                IResult::Class(class) => {
+                    // methods of a subclass see `super` in an enclosing scope
+                    closure = Environment::new_with_parent(closure);
+                    closure.borrow_mut().define(IResult::Class(class.clone()));
+                    superclass = Some(class);
                 }
+                _ => {
+                    return Err(RuntimeError::new(
+                        decl.identifier.clone(),
+                        RuntimeErrorKind::InvalidSuperclass,
+                        "superclass must be a class",
+                    )
+                    .with_span(expr.span())
+                    .into())
+                }
+            }
+        }
 
-                let result = visitor.visit_block_stmt(&decl.body);
-                visitor.set_environment(original_env);
-                result
-            }),
-        };
-        self.environment
-            .borrow_mut()
-            .declare_and_init(identifier, IResult::Callable(Shared::new(callable)));
+        self.allocate(OBJECT_SIZE, &decl.identifier, decl.identifier.span)?;
+        let class = LoxClass::new(
+            decl.identifier.lexeme.as_str(),
+            superclass,
+            decl.methods.clone(),
+            closure,
+        );
+        self.define(
+            &decl.identifier.lexeme,
+            Some(IResult::Class(Gc::new(class))),
+        );
+
+        Ok(None)
+    }
 
-        None
+    fn visit_get(&mut self, get: &ast::Get) -> EvalResult {
+        let object = self.visit_expression(&get.object)?;
+        if let IResult::Instance(instance) = &object {
+            let name = get.name.lexeme.as_str();
+            if let Some(value) = instance.get_field(name) {
+                return Ok(value);
+            }
+            if let Some(bound) = instance.class.bind(name, object.clone()) {
+                self.allocate(OBJECT_SIZE, &get.name, get.name.span)?;
+                return Ok(IResult::Callable(Gc::new(bound)));
+            }
+            self.error(
+                &get.name,
+                RuntimeErrorKind::UndefinedProperty,
+                format!("undefined property '{}'", name).as_str(),
+            )
+        } else {
+            Err(RuntimeError::new(
+                get.name.clone(),
+                RuntimeErrorKind::NotAnInstance,
+                "only instances have properties",
+            )
+            .with_span(get.object.span())
+            .into())
+        }
     }
 
-    fn visit_return_stmt(&mut self, stmt: &ast::ReturnStmt) -> IResult {
-        IResult::Return(Rc::new(if let Some(value) = &stmt.value {
-            self.visit_expression(&value)
+    fn visit_set(&mut self, set: &ast::Set) -> EvalResult {
+        let object = self.visit_expression(&set.object)?;
+        if let IResult::Instance(instance) = object {
+            let value = self.visit_expression(&set.value)?;
+            instance
+                .borrow_mut()
+                .set_field(set.name.lexeme.as_str(), value.clone());
+            Ok(value)
         } else {
-            None
-        }))
+            Err(RuntimeError::new(
+                set.name.clone(),
+                RuntimeErrorKind::NotAnInstance,
+                "only instances have fields",
+            )
+            .with_span(set.object.span())
+            .into())
+        }
+    }
+
+    fn visit_super(&mut self, sup: &ast::Super) -> EvalResult {
+        let Some(binding) = sup.binding.get() else {
+            return self.error(
+                &sup.keyword,
+                RuntimeErrorKind::UndefinedVariable,
+                "can't use 'super' outside of a subclass",
+            );
+        };
+        let superclass = self
+            .environment
+            .get_at(binding.depth, binding.slot)
+            .unwrap();
+        let IResult::Class(superclass) = superclass.as_ref() else {
+            unreachable!("'super' is always bound to a class")
+        };
+        // `this` is bound in the scope right inside the one holding `super`
+        let instance = self
+            .environment
+            .get_at(binding.depth - 1, 0)
+            .unwrap()
+            .as_ref()
+            .clone();
+        match superclass.bind(sup.method.lexeme.as_str(), instance) {
+            Some(method) => {
+                self.allocate(OBJECT_SIZE, &sup.method, sup.span)?;
+                Ok(IResult::Callable(Gc::new(method)))
+            }
+            Option::None => Err(RuntimeError::new(
+                sup.method.clone(),
+                RuntimeErrorKind::UndefinedProperty,
+                format!("undefined property '{}'", sup.method.lexeme).as_str(),
+            )
+            .with_span(sup.span)
+            .into()),
+        }
+    }
+
+    fn visit_this(&mut self, this: &ast::This) -> EvalResult {
+        match this.binding.get() {
+            Some(binding) => Ok(self
+                .environment
+                .get_at(binding.depth, binding.slot)
+                .unwrap()
+                .as_ref()
+                .clone()),
+            Option::None => self.error(
+                &this.keyword,
+                RuntimeErrorKind::UndefinedVariable,
+                "can't use 'this' outside of a class",
+            ),
+        }
+    }
+
+    fn visit_lambda(&mut self, lambda: &ast::Lambda) -> EvalResult {
+        self.allocate(
+            OBJECT_SIZE,
+            &lambda.function.identifier,
+            lambda.function.span,
+        )?;
+        // closes over the current environment, like a function declaration
+        let callable =
+            LoxCallable::new_function(lambda.function.clone(), self.environment.clone(), false);
+        Ok(IResult::Callable(Gc::new(callable)))
+    }
+
+    fn visit_return_stmt(&mut self, stmt: &ast::ReturnStmt) -> EvalResult {
+        let value = match &stmt.value {
+            Some(value) => self.visit_expression(value)?,
+            Option::None => Nil,
+        };
+        Err(Unwind::Return(value))
     }
 }
 
@@ -420,57 +554,200 @@ impl Interpreter<Stdout> {
 
 impl<T: Write> Interpreter<T> {
     pub fn new_with_out(ostream: Shared<T>) -> Interpreter<T> {
+        Interpreter::new_with_registry(ostream, NativeRegistry::default())
+    }
+
+    // an interpreter whose globals start out with the functions of the registry
+    pub fn new_with_registry(ostream: Shared<T>, natives: NativeRegistry) -> Interpreter<T> {
+        let mut globals = Globals::default();
+        for (name, callable) in natives.into_functions() {
+            globals.define(&name, IResult::Callable(Gc::new(callable)));
+        }
         Interpreter {
             environment: Environment::new(),
+            globals: Shared::new(globals),
             ostream,
+            frames: Vec::new(),
+            budget: Budget::default(),
+            stack_base: 0,
+        }
+    }
+
+    // the limits apply from the next run on
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.budget.limits = limits;
+    }
+
+    // for stopping runs from another thread
+    pub fn interrupt_handle(&self) -> InterruptHandle {
+        self.budget.interrupt.clone()
+    }
+
+    pub fn get_global(&self, name: &str) -> Option<IResult> {
+        self.globals
+            .get(name)
+            .ok()
+            .map(|value| value.as_ref().clone())
+    }
+
+    pub fn set_global(&mut self, name: &str, value: IResult) {
+        self.globals.borrow_mut().define(name, value);
+    }
+
+    // calls a function or class value from outside of any script
+    pub fn call(
+        &mut self,
+        callee: IResult,
+        arguments: Vec<IResult>,
+    ) -> Result<IResult, RuntimeError> {
+        let Some(arity) = arity_of(&callee) else {
+            return Err(RuntimeError::native(
+                RuntimeErrorKind::NotCallable,
+                format!("{} is not callable", callee).as_str(),
+            ));
+        };
+        if arguments.len() != arity {
+            return Err(RuntimeError::native(
+                RuntimeErrorKind::ArityMismatch,
+                format!("expected {} arguments but got {}", arity, arguments.len()).as_str(),
+            ));
         }
+        if self.frames.is_empty() {
+            self.budget.reset();
+            self.stack_base = stack_address();
+        }
+        self.invoke(callee, arguments)
     }
 
-    pub fn interpret(&mut self, decls: Vec<ast::DeclRef>) -> Result<IResult, std::string::String> {
+    pub fn interpret(&mut self, decls: Vec<ast::DeclRef>) -> Result<IResult, RuntimeError> {
         let mut result = IResult::None;
+        self.budget.reset();
+        self.stack_base = stack_address();
+        self.frames.push(CallFrame {
+            function: "<script>".to_string(),
+            line: 0,
+        });
         for decl in decls {
-            match self.interpret_decl(decl) {
-                Ok(IResult::Break) => return Err("break outside loop".to_string()),
-                Err(err) => return Err(err),
+            match self.visit_declaration(decl) {
                 Ok(val) => result = val,
+                Err(unwind) => {
+                    // an error can leave us in any nested scope; start over from the top level
+                    while let Some(parent) = self.environment.parent() {
+                        self.environment = parent;
+                    }
+                    let err = self.with_backtrace(unwind.into_error());
+                    self.frames.clear();
+                    return Err(err);
+                }
             }
         }
+        self.frames.clear();
         Ok(result)
     }
-    fn interpret_decl(&mut self, decl: ast::DeclRef) -> Result<IResult, std::string::String> {
-        let prev = panic::take_hook();
-        panic::set_hook(Box::new(move |info| {
-            if let Some(s) = info.payload().downcast_ref::<std::string::String>() {
-                if s.starts_with(INTERPRETER_ERR_TAG) {
-                    eprintln!("{s:?}");
-                    return;
+
+    // runs a callee whose arity was checked against the arguments
+    fn invoke(
+        &mut self,
+        callee: IResult,
+        arguments: Vec<IResult>,
+    ) -> Result<IResult, RuntimeError> {
+        self.budget.step()?;
+        if let IResult::Class(_) = &callee {
+            // the instance is made before init is entered
+            self.budget.allocate(OBJECT_SIZE)?;
+        }
+        let name = match &callee {
+            IResult::Class(class) => Some(class.name.as_str().into()),
+            IResult::Callable(callable) => callable.name.clone(),
+            _ => Option::None,
+        };
+        let Some(name) = name else {
+            return self.run(callee, arguments);
+        };
+
+        self.budget.enter(self.frames.len() + 1)?;
+        if self.frames.len() == FRAMES_MAX
+            || stack_address().abs_diff(self.stack_base) > NATIVE_STACK_MAX
+        {
+            return Err(RuntimeError::native(
+                RuntimeErrorKind::StackOverflow,
+                "stack overflow",
+            ));
+        }
+        self.frames.push(CallFrame {
+            function: name.to_string(),
+            line: 0,
+        });
+        let result = self
+            .run(callee, arguments)
+            .map_err(|err| self.with_backtrace(err));
+        self.frames.pop();
+        result
+    }
+
+    fn run(&mut self, callee: IResult, arguments: Vec<IResult>) -> Result<IResult, RuntimeError> {
+        match callee {
+            IResult::Class(class) => {
+                let instance = IResult::Instance(Gc::new(LoxInstance::new(class.clone())));
+                match class.bind("init", instance.clone()) {
+                    Some(init) => init.call(self as &mut dyn VisitorEnvironmentAware, arguments),
+                    Option::None => Ok(instance),
                 }
             }
-            prev(info);
-        }));
+            IResult::Callable(callable) => {
+                callable.call(self as &mut dyn VisitorEnvironmentAware, arguments)
+            }
+            _ => unreachable!("callee was checked to be callable"),
+        }
+    }
 
-        let result = panic::catch_unwind(AssertUnwindSafe(|| {
-            let result = self.visit_declaration(decl);
-            if let IResult::Break = result {
-                self.error(&TokenType::Break, "break outside loop");
+    // records the calls the error was raised in, unless an inner call already did
+    fn with_backtrace(&self, mut err: RuntimeError) -> RuntimeError {
+        if err.backtrace.is_empty() {
+            err.backtrace = self.frames.iter().rev().cloned().collect();
+            if let Some(innermost) = err.backtrace.first_mut() {
+                innermost.line = err.span.line;
             }
-            result
-        }));
-        if let Ok(exp) = result {
-            Ok(exp)
-        } else {
-            //Err("".to_string())
-            Err(result
-                .unwrap_err()
-                .downcast::<std::string::String>()
-                .unwrap()
-                .to_string())
+        }
+        err
+    }
+
+    // top level declarations become globals, others take the next slot of the current scope
+    fn define(&mut self, name: &str, value: Option<IResult>) {
+        let mut globals = self.globals.borrow_mut();
+        let mut environment = self.environment.borrow_mut();
+        match value {
+            Some(value) if environment.is_global_scope() => globals.define(name, value),
+            Option::None if environment.is_global_scope() => globals.declare(name),
+            Some(value) => environment.define(value),
+            Option::None => environment.declare(),
         }
     }
 
-    fn error(&self, _ttype: &TokenType, errmsg: &str) -> ! {
-        //diverging function
-        //eprintln!("error for {:?}: {}", ttype, errmsg);
-        panic!("{}{}", INTERPRETER_ERR_TAG, errmsg);
+    // counts memory taken by the script against the memory limit
+    fn allocate(&mut self, bytes: usize, token: &TokenRef, span: Span) -> Result<(), RuntimeError> {
+        self.budget
+            .allocate(bytes)
+            .map_err(|err| err.or_at(token, span))
     }
+
+    fn error(&self, token: &TokenRef, kind: RuntimeErrorKind, errmsg: &str) -> EvalResult {
+        Err(RuntimeError::new(token.clone(), kind, errmsg).into())
+    }
+}
+
+// number of arguments a callable value takes; None for values that can't be called
+fn arity_of(callee: &IResult) -> Option<usize> {
+    match callee {
+        IResult::Callable(callable) => Some(callable.arity),
+        IResult::Class(class) => Some(class.arity()),
+        _ => Option::None,
+    }
+}
+
+// roughly how deep the native stack of the calling function is
+#[inline(never)]
+fn stack_address() -> usize {
+    let marker = 0u8;
+    std::hint::black_box(&marker) as *const u8 as usize
 }
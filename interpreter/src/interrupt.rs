@@ -0,0 +1,29 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+// Stops a running script from another thread. Clones share the same flag; the
+// interpreter polls it at loop iterations and calls, and stops the run with a
+// RuntimeErrorKind::Interrupted error. Runs clear it when they start, so an
+// interrupt requested while nothing runs has no effect.
+#[derive(Clone, Debug, Default)]
+pub struct InterruptHandle {
+    requested: Arc<AtomicBool>,
+}
+
+impl InterruptHandle {
+    pub fn interrupt(&self) {
+        self.requested.store(true, Ordering::Relaxed);
+    }
+
+    pub fn clear(&self) {
+        self.requested.store(false, Ordering::Relaxed);
+    }
+
+    // clears the request, so that the interpreter can run again after stopping
+    #[inline]
+    pub fn take(&self) -> bool {
+        self.requested.load(Ordering::Relaxed) && self.requested.swap(false, Ordering::Relaxed)
+    }
+}
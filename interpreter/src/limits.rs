@@ -0,0 +1,88 @@
+use crate::{
+    error::{RuntimeError, RuntimeErrorKind},
+    interrupt::InterruptHandle,
+};
+
+// Bounds on the work and memory of a script, for running untrusted code. Limits
+// left unset don't apply.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Limits {
+    // loop iterations and calls, counted together
+    pub max_steps: Option<u64>,
+    // calls running at once, the script itself included; only lower limits than
+    // `FRAMES_MAX` have an effect
+    pub max_call_depth: Option<usize>,
+    // bytes the script allocates: the length of every string it builds, and
+    // `OBJECT_SIZE` for every function, class, instance and bound method it creates.
+    // Memory freed again still counts.
+    pub max_memory: Option<u64>,
+}
+
+// what functions, classes, instances and bound methods count against `max_memory`;
+// about what one takes on either backend
+pub const OBJECT_SIZE: usize = 64;
+
+// The limits and how much of them a run used up. Runs started by the host count
+// from zero again, and drop interrupts requested before they started.
+#[derive(Default)]
+pub struct Budget {
+    pub limits: Limits,
+    // polled along with the steps
+    pub interrupt: InterruptHandle,
+    steps: u64,
+    allocated: u64,
+}
+
+impl Budget {
+    pub fn reset(&mut self) {
+        self.steps = 0;
+        self.allocated = 0;
+        self.interrupt.clear();
+    }
+
+    // loop iterations and calls, where running scripts can be stopped
+    #[inline]
+    pub fn step(&mut self) -> Result<(), RuntimeError> {
+        if self.interrupt.take() {
+            return Err(RuntimeError::native(
+                RuntimeErrorKind::Interrupted,
+                "script interrupted",
+            ));
+        }
+        self.steps += 1;
+        match self.limits.max_steps {
+            Some(max) if self.steps > max => {
+                Err(exceeded(format!("step limit of {} exceeded", max)))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // depth counts the call being entered
+    #[inline]
+    pub fn enter(&self, depth: usize) -> Result<(), RuntimeError> {
+        match self.limits.max_call_depth {
+            Some(max) if depth > max => {
+                Err(exceeded(format!("call depth limit of {} exceeded", max)))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // charged before the memory is taken, so that a run can't go far over the limit
+    #[inline]
+    pub fn allocate(&mut self, bytes: usize) -> Result<(), RuntimeError> {
+        self.allocated = self.allocated.saturating_add(bytes as u64);
+        match self.limits.max_memory {
+            Some(max) if self.allocated > max => {
+                Err(exceeded(format!("memory limit of {} bytes exceeded", max)))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+// raised like a native error; the interpreters point it at what went over the limit
+fn exceeded(message: String) -> RuntimeError {
+    RuntimeError::native(RuntimeErrorKind::ResourceLimitExceeded, &message)
+}
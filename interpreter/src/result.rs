@@ -1,18 +1,86 @@
-use std::{fmt::Display, rc::Rc};
+use std::{
+    any::Any,
+    fmt::{Debug, Display},
+    rc::Rc,
+};
 
-use rustcore::Shared;
+use rustcore::{Gc, Trace, Tracer};
 
-use crate::callable::LoxCallable;
+use crate::{
+    callable::LoxCallable,
+    class::{LoxClass, LoxInstance},
+};
 
 #[derive(Debug, PartialEq)]
 pub enum IResult {
     Number(f64),
     String(Rc<String>),
     Bool(bool),
+    Nil,
+    // result of evaluating a statement, not a value
     None,
-    Break,
-    Callable(Shared<LoxCallable>),
-    Return(Rc<IResult>),
+    Callable(Gc<LoxCallable>),
+    Class(Gc<LoxClass>),
+    Instance(Gc<LoxInstance>),
+    // only created by native functions; scripts have no list syntax
+    List(Rc<Vec<IResult>>),
+    // a value the interpreter knows nothing about, such as an object of the bytecode vm
+    Foreign(Rc<dyn ForeignObject>),
+}
+
+// Values owned by something other than the interpreter. They can be passed around,
+// printed and compared by identity, but not inspected by scripts.
+pub trait ForeignObject: Display {
+    // name of the type reported by `type`
+    fn type_name(&self) -> &'static str;
+    fn as_any(&self) -> &dyn Any;
+}
+
+impl Debug for dyn ForeignObject {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Foreign({})", self)
+    }
+}
+
+impl PartialEq for dyn ForeignObject {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::addr_eq(self, other)
+    }
+}
+
+impl IResult {
+    // nil and false are falsey, every other value is truthy
+    pub fn is_truthy(&self) -> bool {
+        !matches!(self, Self::Nil | Self::Bool(false) | Self::None)
+    }
+
+    // name of the type of the value, as reported to scripts
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Self::Number(_) => "number",
+            Self::String(_) => "string",
+            Self::Bool(_) => "bool",
+            Self::Callable(_) => "function",
+            Self::Class(_) => "class",
+            Self::Instance(_) => "instance",
+            Self::List(_) => "list",
+            Self::Foreign(object) => object.type_name(),
+            _ => "nil",
+        }
+    }
+}
+
+// Lists and foreign objects aren't looked into, so cycles going through them are
+// never collected.
+impl Trace for IResult {
+    fn trace(&self, tracer: &mut Tracer) {
+        match self {
+            Self::Callable(callable) => tracer.edge(callable),
+            Self::Class(class) => tracer.edge(class),
+            Self::Instance(instance) => tracer.edge(instance),
+            _ => {}
+        }
+    }
 }
 
 impl Clone for IResult {
@@ -21,10 +89,13 @@ impl Clone for IResult {
             Self::Number(arg0) => Self::Number(*arg0),
             Self::String(arg0) => Self::String(arg0.clone()),
             Self::Bool(arg0) => Self::Bool(*arg0),
+            Self::Nil => Self::Nil,
             Self::None => Self::None,
-            Self::Break => Self::Break,
             Self::Callable(arg0) => Self::Callable(arg0.clone()),
-            Self::Return(arg0) => Self::Return(arg0.clone()),
+            Self::Class(arg0) => Self::Class(arg0.clone()),
+            Self::Instance(arg0) => Self::Instance(arg0.clone()),
+            Self::List(arg0) => Self::List(arg0.clone()),
+            Self::Foreign(arg0) => Self::Foreign(arg0.clone()),
         }
     }
 }
@@ -35,10 +106,22 @@ impl Display for IResult {
             Self::Number(n) => write!(f, "{}", n),
             Self::String(s) => write!(f, "{}", s),
             Self::Bool(b) => write!(f, "{}", b),
+            Self::Nil => write!(f, "nil"),
             Self::None => write!(f, ""),
-            Self::Break => write!(f, "break"),
             Self::Callable(c) => write!(f, "{}", c),
-            Self::Return(r) => write!(f, "<return>{}", r),
+            Self::Class(c) => write!(f, "{}", c.name),
+            Self::Instance(i) => write!(f, "{} instance", i.class.name),
+            Self::List(items) => {
+                write!(f, "[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                write!(f, "]")
+            }
+            Self::Foreign(object) => write!(f, "{}", object),
         }
     }
 }
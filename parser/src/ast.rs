@@ -1,7 +1,19 @@
 use as_any::AsAny;
-use std::rc::Rc;
+use std::{cell::Cell, rc::Rc};
 
-use scanner::tokens::TokenRef;
+use scanner::tokens::{Span, TokenRef};
+
+// where the resolver found the declaration a variable reference points to: the
+// number of scopes between the reference and the declaration, and the position
+// of the declaration within its scope
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Binding {
+    pub depth: usize,
+    pub slot: usize,
+}
+
+// filled in by the resolver; references left unresolved are globals
+pub type BindingCell = Cell<Option<Binding>>;
 
 // expression
 pub enum ElementType {
@@ -12,10 +24,17 @@ pub enum ElementType {
     Assign,
     Logical,
     Call,
+    Get,
+    Set,
+    This,
+    Super,
+    Lambda,
 }
 
 pub trait ExprT: AsAny {
     fn element_type(&self) -> ElementType;
+    // source text covered by the expression
+    fn span(&self) -> Span;
 }
 
 pub type ExprRef = Rc<Expression>;
@@ -29,10 +48,13 @@ pub enum StmtType {
     If,
     While,
     Break,
+    Continue,
     Return,
 }
 pub trait StmtT: DeclT {
     fn stmt_type(&self) -> StmtType;
+    // source text covered by the statement
+    fn span(&self) -> Span;
 }
 
 // declaration
@@ -45,6 +67,7 @@ pub enum DeclType {
     Var,
     Stmt,
     Fun,
+    Class,
 }
 
 pub trait DeclT: AsAny {
@@ -73,11 +96,13 @@ pub type DeclRef = Rc<dyn DeclT>;
 pub struct VarDecl {
     pub identifier: TokenRef,
     pub rhs: Option<ExprRef>,
+    pub span: Span,
 }
 
 impl VarDecl {
     pub fn new(identifier: TokenRef) -> Self {
         Self {
+            span: identifier.span,
             identifier,
             rhs: None,
         }
@@ -85,6 +110,7 @@ impl VarDecl {
 
     pub fn new_with_assign(identifier: TokenRef, assign: ExprRef) -> Self {
         Self {
+            span: identifier.span.to(assign.span()),
             identifier,
             rhs: Some(assign),
         }
@@ -115,10 +141,17 @@ impl DeclT for StmtDecl {
     }
 }
 
+impl StmtDecl {
+    pub fn span(&self) -> Span {
+        self.stmt.span()
+    }
+}
+
 impl From<Rc<dyn DeclT>> for StmtDecl {
     fn from(decl: Rc<dyn DeclT>) -> StmtDecl {
         StmtDecl {
             stmt: decl
+                .as_ref()
                 .as_any()
                 .downcast_ref::<StmtDecl>()
                 .unwrap()
@@ -132,6 +165,7 @@ pub struct FunDecl {
     pub identifier: TokenRef,
     pub params: Vec<TokenRef>,
     pub body: BlockStmt,
+    pub span: Span,
 }
 
 impl Clone for FunDecl {
@@ -140,6 +174,7 @@ impl Clone for FunDecl {
             identifier: self.identifier.clone(),
             params: self.params.clone(),
             body: self.body.clone(),
+            span: self.span,
         }
     }
 }
@@ -153,33 +188,62 @@ impl DeclT for FunDecl {
     }
 }
 
+pub struct ClassDecl {
+    pub identifier: TokenRef,
+    pub superclass: Option<Expression>,
+    pub methods: Vec<Rc<FunDecl>>,
+    pub span: Span,
+}
+
+impl DeclT for ClassDecl {
+    fn decl_type(&self) -> DeclType {
+        DeclType::Class
+    }
+    fn as_decl_type(self: Rc<Self>) -> Rc<dyn DeclT> {
+        self
+    }
+}
+
 // statements
 pub struct ExprStmt {
     pub value: ExprRef,
+    pub span: Span,
 }
 impl StmtT for ExprStmt {
     fn stmt_type(&self) -> StmtType {
         StmtType::Expression
     }
+
+    fn span(&self) -> Span {
+        self.span
+    }
 }
 
 pub struct PrintStmt {
+    pub keyword: TokenRef,
     pub value: ExprRef,
+    pub span: Span,
 }
 impl StmtT for PrintStmt {
     fn stmt_type(&self) -> StmtType {
         StmtType::Print
     }
+
+    fn span(&self) -> Span {
+        self.span
+    }
 }
 
 pub struct BlockStmt {
     pub declarations: Rc<Vec<DeclRef>>,
+    pub span: Span,
 }
 
 impl Clone for BlockStmt {
     fn clone(&self) -> Self {
         Self {
             declarations: self.declarations.clone(),
+            span: self.span,
         }
     }
 }
@@ -188,47 +252,93 @@ impl StmtT for BlockStmt {
     fn stmt_type(&self) -> StmtType {
         StmtType::Block
     }
+
+    fn span(&self) -> Span {
+        self.span
+    }
 }
 
 pub struct IfStmt {
+    pub keyword: TokenRef,
     pub condition: ExprRef,
     pub then_b: StmtDecl,
     pub else_b: Option<StmtDecl>,
+    pub span: Span,
 }
 
 impl StmtT for IfStmt {
     fn stmt_type(&self) -> StmtType {
         StmtType::If
     }
+
+    fn span(&self) -> Span {
+        self.span
+    }
 }
 
 pub struct WhileStmt {
+    pub keyword: TokenRef,
     pub condition: ExprRef,
     pub body: BlockStmt,
+    // the increment clause of a for loop; runs after the body, even when it continues
+    pub increment: Option<ExprRef>,
+    pub span: Span,
 }
 
 impl StmtT for WhileStmt {
     fn stmt_type(&self) -> StmtType {
         StmtType::While
     }
+
+    fn span(&self) -> Span {
+        self.span
+    }
 }
 
-pub struct BreakStmt;
+pub struct BreakStmt {
+    pub keyword: TokenRef,
+    pub span: Span,
+}
 
 impl StmtT for BreakStmt {
     fn stmt_type(&self) -> StmtType {
         StmtType::Break
     }
+
+    fn span(&self) -> Span {
+        self.span
+    }
+}
+
+pub struct ContinueStmt {
+    pub keyword: TokenRef,
+    pub span: Span,
+}
+
+impl StmtT for ContinueStmt {
+    fn stmt_type(&self) -> StmtType {
+        StmtType::Continue
+    }
+
+    fn span(&self) -> Span {
+        self.span
+    }
 }
 
 pub struct ReturnStmt {
+    pub keyword: TokenRef,
     pub value: Option<ExprRef>,
+    pub span: Span,
 }
 
 impl StmtT for ReturnStmt {
     fn stmt_type(&self) -> StmtType {
         StmtType::Return
     }
+
+    fn span(&self) -> Span {
+        self.span
+    }
 }
 
 // expression
@@ -237,50 +347,87 @@ pub struct Expression {
     pub value: Rc<dyn ExprT>,
 }
 
+impl Expression {
+    pub fn span(&self) -> Span {
+        self.value.span()
+    }
+}
+
 // literals
 pub struct Literal {
     pub value: TokenRef,
+    // only meaningful for identifiers
+    pub binding: BindingCell,
+}
+
+impl Literal {
+    pub fn new(value: TokenRef) -> Self {
+        Self {
+            value,
+            binding: BindingCell::default(),
+        }
+    }
 }
 
 impl ExprT for Literal {
     fn element_type(&self) -> ElementType {
         ElementType::Literal
     }
+
+    fn span(&self) -> Span {
+        self.value.span
+    }
 }
 
 // grouping
 pub struct Grouping {
     pub expr: Expression,
+    pub span: Span,
 }
 
 impl ExprT for Grouping {
     fn element_type(&self) -> ElementType {
         ElementType::Grouping
     }
+
+    fn span(&self) -> Span {
+        self.span
+    }
 }
 
 // unary
 pub struct Unary {
     pub operator: TokenRef,
     pub expr: Expression,
+    pub span: Span,
 }
 
 impl ExprT for Unary {
     fn element_type(&self) -> ElementType {
         ElementType::Unary
     }
+
+    fn span(&self) -> Span {
+        self.span
+    }
 }
 
 // call
 pub struct Call {
     pub callee: Expression,
+    pub paren: TokenRef,
     pub arguments: Vec<Expression>,
+    pub span: Span,
 }
 
 impl ExprT for Call {
     fn element_type(&self) -> ElementType {
         ElementType::Call
     }
+
+    fn span(&self) -> Span {
+        self.span
+    }
 }
 
 // binary
@@ -288,24 +435,35 @@ pub struct Binary {
     pub left: Expression,
     pub operator: TokenRef,
     pub right: Expression,
+    pub span: Span,
 }
 
 impl ExprT for Binary {
     fn element_type(&self) -> ElementType {
         ElementType::Binary
     }
+
+    fn span(&self) -> Span {
+        self.span
+    }
 }
 
 // assignment
 pub struct Assign {
     pub identifier: TokenRef,
     pub value: Expression,
+    pub span: Span,
+    pub binding: BindingCell,
 }
 
 impl ExprT for Assign {
     fn element_type(&self) -> ElementType {
         ElementType::Assign
     }
+
+    fn span(&self) -> Span {
+        self.span
+    }
 }
 
 // Logical expression
@@ -313,10 +471,100 @@ pub struct Logical {
     pub left: Expression,
     pub operator: TokenRef,
     pub right: Expression,
+    pub span: Span,
 }
 
 impl ExprT for Logical {
     fn element_type(&self) -> ElementType {
         ElementType::Logical
     }
+
+    fn span(&self) -> Span {
+        self.span
+    }
+}
+
+// property access
+pub struct Get {
+    pub object: Expression,
+    pub name: TokenRef,
+    pub span: Span,
+}
+
+impl ExprT for Get {
+    fn element_type(&self) -> ElementType {
+        ElementType::Get
+    }
+
+    fn span(&self) -> Span {
+        self.span
+    }
+}
+
+// property assignment
+pub struct Set {
+    pub object: Expression,
+    pub name: TokenRef,
+    pub value: Expression,
+    pub span: Span,
+}
+
+impl ExprT for Set {
+    fn element_type(&self) -> ElementType {
+        ElementType::Set
+    }
+
+    fn span(&self) -> Span {
+        self.span
+    }
+}
+
+// this
+pub struct This {
+    pub keyword: TokenRef,
+    pub binding: BindingCell,
+}
+
+impl ExprT for This {
+    fn element_type(&self) -> ElementType {
+        ElementType::This
+    }
+
+    fn span(&self) -> Span {
+        self.keyword.span
+    }
+}
+
+// superclass method access
+pub struct Super {
+    pub keyword: TokenRef,
+    pub method: TokenRef,
+    pub span: Span,
+    pub binding: BindingCell,
+}
+
+impl ExprT for Super {
+    fn element_type(&self) -> ElementType {
+        ElementType::Super
+    }
+
+    fn span(&self) -> Span {
+        self.span
+    }
+}
+
+// anonymous function: fun (a, b) { ... }
+pub struct Lambda {
+    pub keyword: TokenRef,
+    pub function: Rc<FunDecl>,
+}
+
+impl ExprT for Lambda {
+    fn element_type(&self) -> ElementType {
+        ElementType::Lambda
+    }
+
+    fn span(&self) -> Span {
+        self.function.span
+    }
 }
@@ -0,0 +1,57 @@
+use std::fmt::Display;
+
+use scanner::{
+    diagnostics::Diagnostic,
+    tokens::{Span, TokenRef, TokenType},
+};
+
+#[derive(Clone, Debug)]
+pub struct ParseError {
+    pub token: TokenRef,
+    pub line: u32,
+    pub message: String,
+    pub notes: Vec<String>,
+}
+
+impl ParseError {
+    pub fn new(token: TokenRef, message: &str) -> Self {
+        Self {
+            line: token.line_num,
+            token,
+            message: message.to_string(),
+            notes: Vec::new(),
+        }
+    }
+
+    pub fn with_note(mut self, note: &str) -> Self {
+        self.notes.push(note.to_string());
+        self
+    }
+
+    pub fn span(&self) -> Span {
+        self.token.span
+    }
+
+    pub fn diagnostic(&self) -> Diagnostic {
+        self.notes.iter().fold(
+            Diagnostic::new(&self.message, self.span()),
+            |diagnostic, note| diagnostic.with_note(note),
+        )
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.token.ttype == TokenType::Eof {
+            write!(f, "[line {}] Error at end: {}", self.line, self.message)
+        } else {
+            write!(
+                f,
+                "[line {}] Error at '{}': {}",
+                self.line, self.token.lexeme, self.message
+            )
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
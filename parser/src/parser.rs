@@ -1,30 +1,32 @@
 pub mod ast;
+pub mod error;
 pub mod printer;
+pub mod resolver;
 pub mod utils;
 
-use std::{
-    panic::{self, AssertUnwindSafe},
-    rc::Rc,
-};
+use std::rc::Rc;
 
 use crate::ast::{
-    Binary, Call, ExprStmt, Expression, Grouping, Literal, Logical, PrintStmt, Unary,
+    Binary, BindingCell, Call, ClassDecl, ExprStmt, Expression, Get, Grouping, Lambda, Literal,
+    Logical, PrintStmt, Set, Super, This, Unary,
 };
 
 use utils::expr_utils::wrap_expr;
 
 use ast::{
-    Assign, BlockStmt, BreakStmt, DeclRef, FunDecl, IfStmt, ReturnStmt, StmtDecl, VarDecl,
-    WhileStmt,
+    Assign, BlockStmt, BreakStmt, ContinueStmt, DeclRef, FunDecl, IfStmt, ReturnStmt, StmtDecl,
+    StmtT, VarDecl, WhileStmt,
 };
-use scanner::tokens::{TokenRef, TokenType};
+use error::ParseError;
+use scanner::tokens::{Span, Token, TokenRef, TokenType};
 
-static PARSER_ERR_TAG: &str = "PARSER_ERROR:";
+type ParseResult<T> = Result<T, ParseError>;
 
 #[allow(dead_code)]
 pub struct Parser {
     pub tokens: Vec<TokenRef>,
     token_cursor: usize,
+    errors: Vec<ParseError>,
 }
 
 #[allow(dead_code)]
@@ -33,218 +35,418 @@ impl Parser {
         Parser {
             tokens,
             token_cursor: 0,
+            errors: Vec::new(),
         }
     }
 
-    pub fn parse(&mut self) -> Result<Vec<DeclRef>, String> {
-        // special handling based on parser error tag
-        let prev = panic::take_hook();
-        panic::set_hook(Box::new(move |info| {
-            if let Some(s) = info.payload().downcast_ref::<String>() {
-                if s.starts_with(PARSER_ERR_TAG) {
-                    eprintln!("{s:?}");
-                    return;
-                }
+    pub fn parse(&mut self) -> Result<Vec<DeclRef>, Vec<ParseError>> {
+        let mut stmts = Vec::new();
+        while !self.is_end() {
+            if let Some(decl) = self.declaration() {
+                stmts.push(decl);
             }
-            prev(info);
-        }));
+        }
 
-        match panic::catch_unwind(AssertUnwindSafe(|| {
-            let mut stmts = Vec::new();
-            while !self.is_end() {
-                stmts.push(self.declaration());
+        if self.errors.is_empty() {
+            Ok(stmts)
+        } else {
+            Err(std::mem::take(&mut self.errors))
+        }
+    }
+
+    // parses a declaration, recovering at the next statement boundary on error.
+    // The error is recorded and None is returned so that parsing can continue.
+    fn declaration(&mut self) -> Option<DeclRef> {
+        match self.try_declaration() {
+            Ok(decl) => Some(decl),
+            Err(err) => {
+                self.errors.push(err);
+                self.synchronize();
+                None
             }
-            stmts
-        })) {
-            Ok(stmts) => Ok(stmts),
-            Err(_) => Err("".to_string()),
         }
     }
 
-    fn declaration(&mut self) -> DeclRef {
-        if self.match_t(&[TokenType::Var]) {
-            Rc::new(self.var_declaration())
+    fn try_declaration(&mut self) -> ParseResult<DeclRef> {
+        Ok(if self.match_t(&[TokenType::Var]) {
+            Rc::new(self.var_declaration()?)
         } else if self.match_t(&[TokenType::Fun]) {
-            Rc::new(self.fun_declaration())
+            if self.check(&TokenType::LeftBrace) {
+                // an expression statement starting with a lambda
+                self.retreat();
+                Rc::new(self.statement()?)
+            } else {
+                Rc::new(self.fun_declaration()?)
+            }
+        } else if self.match_t(&[TokenType::Class]) {
+            Rc::new(self.class_declaration()?)
         } else {
-            Rc::new(self.statement()) //.as_decl_type()
-        }
+            Rc::new(self.statement()?) //.as_decl_type()
+        })
     }
 
-    fn fun_declaration(&mut self) -> FunDecl {
+    fn fun_declaration(&mut self) -> ParseResult<FunDecl> {
         // 'fun' is already matched
+        let start = self.previous().span;
+        self.function(start, "expected identifier after 'fun'")
+    }
+
+    fn class_declaration(&mut self) -> ParseResult<ClassDecl> {
+        // 'class' is already matched
+        let start = self.previous().span;
+        if !self.match_t(&[TokenType::Identifier]) {
+            return Err(self.error("expected identifier after 'class'"));
+        }
+        let identifier = self.previous();
+
+        let mut superclass = None;
+        if self.match_t(&[TokenType::Less]) {
+            if !self.match_t(&[TokenType::Identifier]) {
+                return Err(self.error("expected superclass name after '<'"));
+            }
+            let name = self.previous();
+            if name.lexeme == identifier.lexeme {
+                return Err(ParseError::new(name, "a class can't inherit from itself"));
+            }
+            superclass = Some(wrap_expr(Literal::new(name)));
+        }
+        self.consume(&TokenType::LeftParen, "expected '{' before class body")?;
+
+        let mut methods = Vec::new();
+        while !self.check(&TokenType::RightParen) && !self.is_end() {
+            let method_start = self.peek().span;
+            methods.push(Rc::new(
+                self.function(method_start, "expected method name")?,
+            ));
+        }
+        self.consume(&TokenType::RightParen, "expected '}' after class body")?;
+
+        Ok(ClassDecl {
+            identifier,
+            superclass,
+            methods,
+            span: self.span_from(start),
+        })
+    }
+
+    // name, parameters and body of a function or method
+    fn function(&mut self, start: Span, errmsg: &str) -> ParseResult<FunDecl> {
         if self.match_t(&[TokenType::Identifier]) {
             let identifier = self.previous();
-            self.consume(&TokenType::LeftBrace, "expected '(' after function name");
-            let mut params = Vec::new();
-            if !self.match_t(&[TokenType::RightBrace]) {
-                loop {
-                    if params.len() >= 255 {
-                        self.error("cannot have more than 255 parameters");
-                    }
-                    if self.match_t(&[TokenType::Identifier]) {
-                        params.push(self.previous());
-                    }
-                    if self.match_t(&[TokenType::Comma]) {
-                        continue;
-                    }
-                    // comma not found, should end with right brace
-                    self.consume(
-                        &TokenType::RightBrace,
-                        "expected paranthesis after parameters",
-                    );
-                    break;
-                }
-            }
+            self.function_rest(identifier, start)
+        } else {
+            Err(self.error(errmsg))
+        }
+    }
 
-            let bstmt = self.block_stmt(false);
-            FunDecl {
-                identifier,
-                params,
-                body: bstmt,
+    // parameters and body of a function whose name has been parsed
+    fn function_rest(&mut self, identifier: TokenRef, start: Span) -> ParseResult<FunDecl> {
+        self.consume(&TokenType::LeftBrace, "expected '(' after function name")?;
+        let mut params = Vec::new();
+        if !self.match_t(&[TokenType::RightBrace]) {
+            loop {
+                if params.len() >= 255 {
+                    return Err(self.error("cannot have more than 255 parameters"));
+                }
+                if self.match_t(&[TokenType::Identifier]) {
+                    params.push(self.previous());
+                }
+                if self.match_t(&[TokenType::Comma]) {
+                    continue;
+                }
+                // comma not found, should end with right brace
+                self.consume(
+                    &TokenType::RightBrace,
+                    "expected paranthesis after parameters",
+                )?;
+                break;
             }
-        } else {
-            self.error("expected identifier after 'fun'")
         }
+
+        let bstmt = self.block_stmt(false)?;
+        Ok(FunDecl {
+            identifier,
+            params,
+            body: bstmt,
+            span: self.span_from(start),
+        })
     }
 
-    fn var_declaration(&mut self) -> VarDecl {
+    fn var_declaration(&mut self) -> ParseResult<VarDecl> {
+        let start = self.previous().span;
         if self.match_t(&[TokenType::Identifier]) {
             let identifier = self.previous();
             let mut rhs = None;
             if self.match_t(&[TokenType::Equal]) {
-                let initializer = self.expression();
+                let initializer = self.expression()?;
                 rhs = Some(Rc::new(initializer))
             }
-            self.consume(&TokenType::Semicolon, "semicolon missing");
-            return VarDecl { identifier, rhs };
+            self.consume(&TokenType::Semicolon, "semicolon missing")?;
+            return Ok(VarDecl {
+                identifier,
+                rhs,
+                span: self.span_from(start),
+            });
         }
-        self.error("expected identifier after 'var'")
+        Err(self.error("expected identifier after 'var'"))
     }
 
-    fn statement(&mut self) -> StmtDecl {
-        StmtDecl {
+    fn statement(&mut self) -> ParseResult<StmtDecl> {
+        Ok(StmtDecl {
             stmt: if self.match_t(&[TokenType::Print]) {
-                Rc::new(self.print_stmt())
+                Rc::new(self.print_stmt()?)
             } else if self.match_t(&[TokenType::LeftParen]) {
-                Rc::new(self.block_stmt(true))
+                Rc::new(self.block_stmt(true)?)
             } else if self.match_t(&[TokenType::If]) {
-                Rc::new(self.if_stmt())
+                Rc::new(self.if_stmt()?)
             } else if self.match_t(&[TokenType::While]) {
-                Rc::new(self.while_stmt())
+                Rc::new(self.while_stmt()?)
+            } else if self.match_t(&[TokenType::For]) {
+                self.for_stmt()?
             } else if self.match_t(&[TokenType::Break]) {
-                Rc::new(self.break_stmt())
+                Rc::new(self.break_stmt()?)
+            } else if self.match_t(&[TokenType::Continue]) {
+                Rc::new(self.continue_stmt()?)
             } else if self.match_t(&[TokenType::Return]) {
-                Rc::new(self.return_stmt())
+                Rc::new(self.return_stmt()?)
             } else {
-                Rc::new(self.expr_stmt())
+                Rc::new(self.expr_stmt()?)
             },
-        }
+        })
     }
 
-    fn break_stmt(&mut self) -> BreakStmt {
+    fn break_stmt(&mut self) -> ParseResult<BreakStmt> {
         // break has been matched
-        self.consume(&TokenType::Semicolon, "semicolon missing");
-        BreakStmt {}
-    }
-
-    fn print_stmt(&mut self) -> PrintStmt {
-        let value = self.expression();
-        self.consume(&TokenType::Semicolon, "semicolon missing");
-        PrintStmt {
+        let keyword = self.previous();
+        self.consume(&TokenType::Semicolon, "semicolon missing")?;
+        Ok(BreakStmt {
+            span: self.span_from(keyword.span),
+            keyword,
+        })
+    }
+
+    fn continue_stmt(&mut self) -> ParseResult<ContinueStmt> {
+        // continue has been matched
+        let keyword = self.previous();
+        self.consume(&TokenType::Semicolon, "semicolon missing")?;
+        Ok(ContinueStmt {
+            span: self.span_from(keyword.span),
+            keyword,
+        })
+    }
+
+    fn print_stmt(&mut self) -> ParseResult<PrintStmt> {
+        let keyword = self.previous();
+        let value = self.expression()?;
+        self.consume(&TokenType::Semicolon, "semicolon missing")?;
+        Ok(PrintStmt {
+            span: self.span_from(keyword.span),
+            keyword,
             value: Rc::new(value),
-        }
+        })
     }
 
-    fn expr_stmt(&mut self) -> ExprStmt {
-        let value = self.expression();
-        self.consume(&TokenType::Semicolon, "semicolon missing");
-        ExprStmt {
+    fn expr_stmt(&mut self) -> ParseResult<ExprStmt> {
+        let start = self.peek().span;
+        let value = self.expression()?;
+        self.consume(&TokenType::Semicolon, "semicolon missing")?;
+        Ok(ExprStmt {
             value: Rc::new(value),
-        }
+            span: self.span_from(start),
+        })
     }
 
-    fn block_stmt(&mut self, ft_consumed: bool) -> BlockStmt {
+    fn block_stmt(&mut self, ft_consumed: bool) -> ParseResult<BlockStmt> {
+        let start = if ft_consumed {
+            self.previous().span
+        } else {
+            self.peek().span
+        };
         if !ft_consumed {
-            self.consume(&TokenType::LeftParen, "expected '{' at start of block");
+            self.consume(&TokenType::LeftParen, "expected '{' at start of block")?;
         }
         let mut decls = Vec::new();
-        while !self.match_t(&[TokenType::RightParen]) {
-            decls.push(self.declaration());
+        while !self.check(&TokenType::RightParen) && !self.is_end() {
+            // errors inside the block are recorded by declaration(); keep going
+            if let Some(decl) = self.declaration() {
+                decls.push(decl);
+            }
         }
-        BlockStmt {
+        self.consume(&TokenType::RightParen, "expected '}' at end of block")?;
+        Ok(BlockStmt {
             declarations: Rc::new(decls),
-        }
+            span: self.span_from(start),
+        })
     }
 
-    fn if_stmt(&mut self) -> IfStmt {
+    fn if_stmt(&mut self) -> ParseResult<IfStmt> {
         // assuming if is already consumed
-        IfStmt {
-            condition: Rc::new(self.expression()),
-            then_b: self.statement(),
-            else_b: if self.match_t(&[TokenType::Else]) {
-                Some(self.statement())
-            } else {
-                None
-            },
-        }
-    }
-
-    fn while_stmt(&mut self) -> WhileStmt {
+        let keyword = self.previous();
+        let condition = Rc::new(self.expression()?);
+        let then_b = self.statement()?;
+        let else_b = if self.match_t(&[TokenType::Else]) {
+            Some(self.statement()?)
+        } else {
+            None
+        };
+        Ok(IfStmt {
+            span: self.span_from(keyword.span),
+            keyword,
+            condition,
+            then_b,
+            else_b,
+        })
+    }
+
+    fn while_stmt(&mut self) -> ParseResult<WhileStmt> {
         // assuming while is already consumed
-        WhileStmt {
-            condition: Rc::new(self.expression()),
-            body: self.block_stmt(false),
-        }
-    }
+        let keyword = self.previous();
+        let condition = Rc::new(self.expression()?);
+        let body = self.block_stmt(false)?;
+        Ok(WhileStmt {
+            span: self.span_from(keyword.span),
+            keyword,
+            condition,
+            body,
+            increment: None,
+        })
+    }
+
+    // a for loop is desugared into a while loop:
+    // { initializer; while condition { body } } with the increment kept on the
+    // while statement, so that it also runs when the body continues
+    fn for_stmt(&mut self) -> ParseResult<Rc<dyn StmtT>> {
+        // assuming for is already consumed
+        let keyword = self.previous();
+        self.consume(&TokenType::LeftBrace, "expected '(' after 'for'")?;
+
+        let initializer: Option<DeclRef> = if self.match_t(&[TokenType::Semicolon]) {
+            None
+        } else if self.match_t(&[TokenType::Var]) {
+            Some(Rc::new(self.var_declaration()?))
+        } else {
+            Some(Rc::new(StmtDecl {
+                stmt: Rc::new(self.expr_stmt()?),
+            }))
+        };
+
+        let condition = if self.check(&TokenType::Semicolon) {
+            // a missing condition loops forever
+            wrap_expr(Literal::new(Rc::new(Token {
+                ttype: TokenType::True,
+                lexeme: "true".to_string(),
+                line_num: keyword.line_num,
+                span: keyword.span,
+            })))
+        } else {
+            self.expression()?
+        };
+        self.consume(&TokenType::Semicolon, "expected ';' after loop condition")?;
 
-    fn return_stmt(&mut self) -> ReturnStmt {
+        let increment = if self.check(&TokenType::RightBrace) {
+            None
+        } else {
+            Some(Rc::new(self.expression()?))
+        };
+        self.consume(&TokenType::RightBrace, "expected ')' after for clauses")?;
+
+        let body = self.block_stmt(false)?;
+        let span = self.span_from(keyword.span);
+        let while_stmt = WhileStmt {
+            keyword,
+            condition: Rc::new(condition),
+            body,
+            increment,
+            span,
+        };
+
+        Ok(match initializer {
+            Some(initializer) => Rc::new(BlockStmt {
+                declarations: Rc::new(vec![
+                    initializer,
+                    Rc::new(StmtDecl {
+                        stmt: Rc::new(while_stmt),
+                    }),
+                ]),
+                span,
+            }),
+            None => Rc::new(while_stmt),
+        })
+    }
+
+    fn return_stmt(&mut self) -> ParseResult<ReturnStmt> {
         // return already consumed
-        ReturnStmt {
-            value: if self.match_t(&[TokenType::Semicolon]) {
-                None
-            } else {
-                let return_val = self.expression();
-                self.consume(&TokenType::Semicolon, "semicolon expected");
-                Some(Rc::new(return_val))
-            },
-        }
-    }
-
-    fn expression(&mut self) -> Expression {
+        let keyword = self.previous();
+        let value = if self.match_t(&[TokenType::Semicolon]) {
+            None
+        } else {
+            let return_val = self.expression()?;
+            self.consume(&TokenType::Semicolon, "semicolon expected")?;
+            Some(Rc::new(return_val))
+        };
+        Ok(ReturnStmt {
+            span: self.span_from(keyword.span),
+            keyword,
+            value,
+        })
+    }
+
+    fn expression(&mut self) -> ParseResult<Expression> {
         self.assignment()
     }
 
-    fn assignment(&mut self) -> Expression {
-        if self.match_t(&[TokenType::Identifier]) {
-            let identifier = self.previous();
-            if self.match_t(&[TokenType::Equal]) {
-                let value = self.assignment();
-                return wrap_expr(Assign { identifier, value });
+    fn assignment(&mut self) -> ParseResult<Expression> {
+        let expr = self.logic_or()?;
+
+        if self.match_t(&[TokenType::Equal]) {
+            // the left hand side was parsed as an expression; only a variable
+            // or a property access can be assigned to
+            let equals = self.previous();
+            let value = self.assignment()?;
+            let span = expr.span().to(value.span());
+            let target = expr.value.as_ref().as_any();
+            if let Some(lit) = target.downcast_ref::<Literal>() {
+                if lit.value.ttype == TokenType::Identifier {
+                    return Ok(wrap_expr(Assign {
+                        identifier: lit.value.clone(),
+                        value,
+                        span,
+                        binding: BindingCell::default(),
+                    }));
+                }
+            } else if let Some(get) = target.downcast_ref::<Get>() {
+                return Ok(wrap_expr(Set {
+                    object: Expression {
+                        value: get.object.value.clone(),
+                    },
+                    name: get.name.clone(),
+                    value,
+                    span,
+                }));
             }
 
-            self.retreat();
+            return Err(ParseError::new(equals, "invalid assignment target"));
         }
 
-        self.logic_or()
+        Ok(expr)
     }
 
-    fn logic_or(&mut self) -> Expression {
+    fn logic_or(&mut self) -> ParseResult<Expression> {
         self.logic_break(|p: &mut Parser| p.logic_and(), &[TokenType::Or])
     }
 
-    fn logic_and(&mut self) -> Expression {
+    fn logic_and(&mut self) -> ParseResult<Expression> {
         self.logic_break(|p: &mut Parser| p.equality(), &[TokenType::And])
     }
 
-    fn equality(&mut self) -> Expression {
+    fn equality(&mut self) -> ParseResult<Expression> {
         self.binary_break(
             |p: &mut Parser| p.comparison(),
             &[TokenType::EqualEqual, TokenType::BangEqual],
         )
     }
 
-    fn comparison(&mut self) -> Expression {
+    fn comparison(&mut self) -> ParseResult<Expression> {
         self.binary_break(
             |p: &mut Parser| p.term(),
             &[
@@ -256,67 +458,126 @@ impl Parser {
         )
     }
 
-    fn term(&mut self) -> Expression {
+    fn term(&mut self) -> ParseResult<Expression> {
         self.binary_break(
             |p: &mut Parser| p.factor(),
             &[TokenType::Minus, TokenType::Plus],
         )
     }
 
-    fn factor(&mut self) -> Expression {
+    fn factor(&mut self) -> ParseResult<Expression> {
         self.binary_break(
             |p: &mut Parser| p.unary(),
             &[TokenType::Slash, TokenType::Star],
         )
     }
 
-    fn unary(&mut self) -> Expression {
+    fn unary(&mut self) -> ParseResult<Expression> {
         if self.match_t(&[TokenType::Bang, TokenType::Minus]) {
             let operator = self.previous();
-            let expr = self.unary();
-            wrap_expr(Unary { operator, expr })
+            let expr = self.unary()?;
+            Ok(wrap_expr(Unary {
+                span: operator.span.to(expr.span()),
+                operator,
+                expr,
+            }))
         } else {
             self.call()
         }
     }
 
-    fn call(&mut self) -> Expression {
-        let mut expr = self.primary();
-        if self.match_t(&[TokenType::LeftBrace]) {
-            let arguments = self.arguments();
-            expr = wrap_expr(Call {
-                callee: expr,
-                arguments,
-            });
+    fn call(&mut self) -> ParseResult<Expression> {
+        let mut expr = self.primary()?;
+        loop {
+            if self.match_t(&[TokenType::LeftBrace]) {
+                let paren = self.previous();
+                let arguments = self.arguments()?;
+                expr = wrap_expr(Call {
+                    span: self.span_from(expr.span()),
+                    callee: expr,
+                    paren,
+                    arguments,
+                });
+            } else if self.match_t(&[TokenType::Dot]) {
+                self.consume(&TokenType::Identifier, "expected property name after '.'")?;
+                expr = wrap_expr(Get {
+                    span: self.span_from(expr.span()),
+                    object: expr,
+                    name: self.previous(),
+                });
+            } else {
+                break;
+            }
         }
 
-        expr
+        Ok(expr)
     }
 
-    fn arguments(&mut self) -> Vec<Expression> {
+    fn arguments(&mut self) -> ParseResult<Vec<Expression>> {
         let mut args = Vec::new();
         if !self.match_t(&[TokenType::RightBrace]) {
             loop {
-                args.push(self.expression());
+                args.push(self.expression()?);
                 if !self.match_t(&[TokenType::Comma]) {
                     break;
                 }
                 if args.len() > 255 {
-                    self.error("too many arguments");
+                    return Err(self.error("too many arguments"));
                 }
             }
 
-            self.consume(&TokenType::RightBrace, "right brace missing");
+            self.consume(&TokenType::RightBrace, "right brace missing")?;
         }
 
-        args
+        Ok(args)
     }
 
-    fn primary(&mut self) -> Expression {
+    fn primary(&mut self) -> ParseResult<Expression> {
         if self.match_t(&[TokenType::LeftBrace]) {
-            let expr = self.expression();
-            self.consume(&TokenType::RightBrace, "right brace missing");
-            return wrap_expr(Grouping { expr });
+            let start = self.previous().span;
+            let expr = self.expression()?;
+            self.consume(&TokenType::RightBrace, "right brace missing")?;
+            return Ok(wrap_expr(Grouping {
+                expr,
+                span: self.span_from(start),
+            }));
+        }
+
+        if self.match_t(&[TokenType::Fun]) {
+            let keyword = self.previous();
+            // anonymous functions show up as <lambda> in backtraces
+            let identifier = Rc::new(Token {
+                ttype: TokenType::Identifier,
+                lexeme: "<lambda>".to_string(),
+                line_num: keyword.line_num,
+                span: keyword.span,
+            });
+            let function = self.function_rest(identifier, keyword.span)?;
+            return Ok(wrap_expr(Lambda {
+                keyword,
+                function: Rc::new(function),
+            }));
+        }
+
+        if self.match_t(&[TokenType::This]) {
+            return Ok(wrap_expr(This {
+                keyword: self.previous(),
+                binding: BindingCell::default(),
+            }));
+        }
+
+        if self.match_t(&[TokenType::Super]) {
+            let keyword = self.previous();
+            self.consume(&TokenType::Dot, "expected '.' after 'super'")?;
+            if !self.match_t(&[TokenType::Identifier]) {
+                return Err(self.error("expected superclass method name"));
+            }
+            return Ok(wrap_expr(Super {
+                span: keyword.span.to(self.previous().span),
+                keyword,
+                method: self.previous(),
+                binding: BindingCell::default(),
+            }));
         }
 
         if self.match_t(&[
@@ -329,51 +590,52 @@ impl Parser {
             TokenType::Return,
         ]) {
             let expr = self.previous();
-            wrap_expr(Literal { value: expr })
+            Ok(wrap_expr(Literal::new(expr)))
         } else {
-            println!("voila:{:?}", self.peek());
-            self.error("literal expected");
+            Err(self.error("literal expected"))
         }
     }
 
     fn binary_break(
         &mut self,
-        gen: fn(&mut Parser) -> Expression,
+        gen: fn(&mut Parser) -> ParseResult<Expression>,
         token_types: &[TokenType],
-    ) -> Expression {
-        let mut expr = gen(self);
+    ) -> ParseResult<Expression> {
+        let mut expr = gen(self)?;
 
         while self.match_t(token_types) {
             let operator = self.previous();
-            let right_expr = gen(self);
+            let right_expr = gen(self)?;
             expr = wrap_expr(Binary {
+                span: expr.span().to(right_expr.span()),
                 left: expr,
                 operator,
                 right: right_expr,
             });
         }
 
-        expr
+        Ok(expr)
     }
 
     fn logic_break(
         &mut self,
-        gen: fn(&mut Parser) -> Expression,
+        gen: fn(&mut Parser) -> ParseResult<Expression>,
         token_types: &[TokenType],
-    ) -> Expression {
-        let mut expr = gen(self);
+    ) -> ParseResult<Expression> {
+        let mut expr = gen(self)?;
 
         while self.match_t(token_types) {
             let operator = self.previous();
-            let right_expr = gen(self);
+            let right_expr = gen(self)?;
             expr = wrap_expr(Logical {
+                span: expr.span().to(right_expr.span()),
                 left: expr,
                 operator,
                 right: right_expr,
             });
         }
 
-        expr
+        Ok(expr)
     }
 
     fn match_t(&mut self, tokens_types: &[TokenType]) -> bool {
@@ -412,21 +674,30 @@ impl Parser {
         self.tokens[self.token_cursor - 1].clone()
     }
 
-    fn consume(&mut self, ttype: &TokenType, errmsg: &str) {
+    fn consume(&mut self, ttype: &TokenType, errmsg: &str) -> ParseResult<()> {
         if self.check(ttype) {
-            return self.advance();
+            self.advance();
+            return Ok(());
         }
 
-        self.error(errmsg);
+        Err(self.error(errmsg))
     }
 
-    fn error(&mut self, errmsg: &str) -> ! {
-        //diverging function
-        //eprintln!("error for {:?}: {}", ttype, errmsg);
-        panic!("{}{}", PARSER_ERR_TAG, errmsg);
+    // span from start up to and including the last consumed token
+    fn span_from(&self, start: Span) -> Span {
+        start.to(self.previous().span)
     }
 
+    fn error(&self, errmsg: &str) -> ParseError {
+        ParseError::new(self.peek(), errmsg)
+    }
+
+    // skip tokens until the start of the next statement, so that a single
+    // mistake doesn't produce a cascade of errors
     fn synchronize(&mut self) {
+        if self.is_end() {
+            return;
+        }
         self.advance();
 
         while !self.is_end() {
@@ -107,13 +107,16 @@ impl Visitor<String> for AstPrinter {
     }
 
     fn visit_while_stmt(&mut self, stmt: &ast::WhileStmt) -> String {
-        let exprs = vec![
+        let mut exprs = vec![
             "while".to_string(),
             self.visit_expression(&stmt.condition),
             "\n{\n".to_string(),
             self.visit_block_stmt(&stmt.body),
             "\n{\n".to_string(),
         ];
+        if let Some(increment) = &stmt.increment {
+            exprs.push(self.visit_expression(increment));
+        }
         self.parenthesize(exprs)
     }
 
@@ -121,6 +124,10 @@ impl Visitor<String> for AstPrinter {
         self.parenthesize(vec!["break".to_string()])
     }
 
+    fn visit_continue_stmt(&mut self, _stmt: &ast::ContinueStmt) -> String {
+        self.parenthesize(vec!["continue".to_string()])
+    }
+
     fn visit_call(&mut self, call: &ast::Call) -> String {
         let mut exprs = vec![self.visit_expression(&call.callee), "(".to_string()];
         for arg in call.arguments.iter() {
@@ -147,6 +154,52 @@ impl Visitor<String> for AstPrinter {
         self.parenthesize(exprs)
     }
 
+    fn visit_class_decl(&mut self, decl: &ast::ClassDecl) -> String {
+        let mut exprs = vec!["class".to_string(), decl.identifier.lexeme.clone()];
+        if let Some(superclass) = &decl.superclass {
+            exprs.push("<".to_string());
+            exprs.push(self.visit_expression(superclass));
+        }
+        exprs.push("{\n".to_string());
+        for method in decl.methods.iter() {
+            exprs.push(self.visit_fun_decl(method.clone()));
+        }
+        exprs.push("\n}".to_string());
+        self.parenthesize(exprs)
+    }
+
+    fn visit_get(&mut self, get: &ast::Get) -> String {
+        let exprs = vec![
+            self.visit_expression(&get.object),
+            ".".to_string(),
+            get.name.lexeme.clone(),
+        ];
+        self.parenthesize(exprs)
+    }
+
+    fn visit_set(&mut self, set: &ast::Set) -> String {
+        let exprs = vec![
+            self.visit_expression(&set.object),
+            ".".to_string(),
+            set.name.lexeme.clone(),
+            "=".to_string(),
+            self.visit_expression(&set.value),
+        ];
+        self.parenthesize(exprs)
+    }
+
+    fn visit_this(&mut self, _this: &ast::This) -> String {
+        "this".to_string()
+    }
+
+    fn visit_super(&mut self, sup: &ast::Super) -> String {
+        format!("super.{}", sup.method.lexeme)
+    }
+
+    fn visit_lambda(&mut self, lambda: &ast::Lambda) -> String {
+        self.visit_fun_decl(lambda.function.clone())
+    }
+
     fn visit_return_stmt(&mut self, stmt: &ast::ReturnStmt) -> String {
         let mut exprs = vec!["<return>".to_string()];
         if let Some(expr) = &stmt.value {
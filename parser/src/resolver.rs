@@ -0,0 +1,357 @@
+use std::{collections::HashMap, rc::Rc};
+
+use crate::{
+    ast::{
+        Assign, Binary, Binding, BindingCell, BlockStmt, BreakStmt, Call, ClassDecl, ContinueStmt,
+        DeclRef, FunDecl, Get, Grouping, IfStmt, Lambda, Literal, Logical, PrintStmt, ReturnStmt,
+        Set, Super, This, Unary, VarDecl, WhileStmt,
+    },
+    error::ParseError,
+    utils::Visitor,
+};
+use scanner::tokens::{TokenRef, TokenType};
+
+#[derive(Clone, Copy, PartialEq)]
+enum FunctionKind {
+    None,
+    Function,
+    Method,
+    Initializer,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum ClassKind {
+    None,
+    Class,
+    Subclass,
+}
+
+struct Local {
+    slot: usize,
+    // false while the initializer of the variable is being resolved
+    defined: bool,
+    // where the variable is declared; nowhere for `this` and `super`
+    line: u32,
+}
+
+// Static pass run between parsing and interpreting. It binds every reference to a
+// local variable to the scope declaring it, so that the interpreter doesn't have to
+// search for names at runtime, and reports the errors that don't need running the
+// program to be found.
+//
+// The scopes opened here mirror the environments created by the interpreter: one
+// per block, one for the parameters of a function (its body being a block of its
+// own), one holding `this` for every bound method and one holding `super` around
+// the methods of a subclass. Globals are not tracked and are looked up by name.
+pub struct Resolver {
+    scopes: Vec<HashMap<String, Local>>,
+    function: FunctionKind,
+    class: ClassKind,
+    // number of loops enclosing the statement being resolved in the current function
+    loop_depth: usize,
+    errors: Vec<ParseError>,
+}
+
+impl Default for Resolver {
+    fn default() -> Self {
+        Resolver::new()
+    }
+}
+
+impl Resolver {
+    pub fn new() -> Self {
+        Self {
+            scopes: Vec::new(),
+            function: FunctionKind::None,
+            class: ClassKind::None,
+            loop_depth: 0,
+            errors: Vec::new(),
+        }
+    }
+
+    pub fn resolve(&mut self, decls: &[DeclRef]) -> Result<(), Vec<ParseError>> {
+        for decl in decls {
+            self.visit_declaration(decl.clone());
+        }
+
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            // leave the resolver usable for the next chunk of code
+            self.scopes.clear();
+            self.loop_depth = 0;
+            Err(std::mem::take(&mut self.errors))
+        }
+    }
+
+    fn begin_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    fn end_scope(&mut self) {
+        self.scopes.pop();
+    }
+
+    fn declare(&mut self, name: &TokenRef) {
+        let Some(scope) = self.scopes.last_mut() else {
+            return;
+        };
+        if let Some(previous) = scope.get(&name.lexeme) {
+            let note = format!(
+                "'{}' is first declared on line {}",
+                name.lexeme, previous.line
+            );
+            let err = ParseError::new(
+                name.clone(),
+                "already a variable with this name in this scope",
+            );
+            self.errors.push(err.with_note(&note));
+            return;
+        }
+        let slot = scope.len();
+        scope.insert(
+            name.lexeme.clone(),
+            Local {
+                slot,
+                defined: false,
+                line: name.span.line,
+            },
+        );
+    }
+
+    fn define(&mut self, name: &TokenRef) {
+        if let Some(local) = self
+            .scopes
+            .last_mut()
+            .and_then(|scope| scope.get_mut(&name.lexeme))
+        {
+            local.defined = true;
+        }
+    }
+
+    // binds an implicitly declared name such as `this` in a scope of its own
+    fn begin_scope_with(&mut self, name: &str) {
+        let mut scope = HashMap::new();
+        scope.insert(
+            name.to_string(),
+            Local {
+                slot: 0,
+                defined: true,
+                line: 0,
+            },
+        );
+        self.scopes.push(scope);
+    }
+
+    fn resolve_local(&mut self, name: &str, binding: &BindingCell) {
+        for (depth, scope) in self.scopes.iter().rev().enumerate() {
+            if let Some(local) = scope.get(name) {
+                binding.set(Some(Binding {
+                    depth,
+                    slot: local.slot,
+                }));
+                return;
+            }
+        }
+        // not found in any scope: a global
+        binding.set(None);
+    }
+
+    fn resolve_function(&mut self, decl: &FunDecl, kind: FunctionKind) {
+        let enclosing_function = std::mem::replace(&mut self.function, kind);
+        // break and continue can't cross function boundaries
+        let enclosing_loop_depth = std::mem::take(&mut self.loop_depth);
+
+        self.begin_scope();
+        for param in decl.params.iter() {
+            self.declare(param);
+            self.define(param);
+        }
+        self.visit_block_stmt(&decl.body);
+        self.end_scope();
+
+        self.function = enclosing_function;
+        self.loop_depth = enclosing_loop_depth;
+    }
+
+    fn error(&mut self, token: &TokenRef, message: &str) {
+        self.errors.push(ParseError::new(token.clone(), message));
+    }
+}
+
+impl Visitor<()> for Resolver {
+    fn visit_var_decl(&mut self, decl: &VarDecl) {
+        self.declare(&decl.identifier);
+        if let Some(rhs) = &decl.rhs {
+            self.visit_expression(rhs);
+        }
+        self.define(&decl.identifier);
+    }
+
+    fn visit_fun_decl(&mut self, decl: Rc<FunDecl>) {
+        // defined straight away so that the function can call itself
+        self.declare(&decl.identifier);
+        self.define(&decl.identifier);
+        self.resolve_function(&decl, FunctionKind::Function);
+    }
+
+    fn visit_class_decl(&mut self, decl: &ClassDecl) {
+        let enclosing_class = std::mem::replace(&mut self.class, ClassKind::Class);
+        self.declare(&decl.identifier);
+        self.define(&decl.identifier);
+
+        if let Some(superclass) = &decl.superclass {
+            self.class = ClassKind::Subclass;
+            self.visit_expression(superclass);
+            self.begin_scope_with("super");
+        }
+
+        for method in decl.methods.iter() {
+            let kind = if method.identifier.lexeme == "init" {
+                FunctionKind::Initializer
+            } else {
+                FunctionKind::Method
+            };
+            self.begin_scope_with("this");
+            self.resolve_function(method, kind);
+            self.end_scope();
+        }
+
+        if decl.superclass.is_some() {
+            self.end_scope();
+        }
+        self.class = enclosing_class;
+    }
+
+    fn visit_print_stmt(&mut self, stmt: &PrintStmt) {
+        self.visit_expression(&stmt.value);
+    }
+
+    fn visit_block_stmt(&mut self, stmt: &BlockStmt) {
+        self.begin_scope();
+        for decl in stmt.declarations.iter() {
+            self.visit_declaration(decl.clone());
+        }
+        self.end_scope();
+    }
+
+    fn visit_if_stmt(&mut self, stmt: &IfStmt) {
+        self.visit_expression(&stmt.condition);
+        self.visit_statement(&stmt.then_b);
+        if let Some(else_b) = &stmt.else_b {
+            self.visit_statement(else_b);
+        }
+    }
+
+    fn visit_while_stmt(&mut self, stmt: &WhileStmt) {
+        self.visit_expression(&stmt.condition);
+        self.loop_depth += 1;
+        self.visit_block_stmt(&stmt.body);
+        self.loop_depth -= 1;
+        if let Some(increment) = &stmt.increment {
+            self.visit_expression(increment);
+        }
+    }
+
+    fn visit_break_stmt(&mut self, stmt: &BreakStmt) {
+        if self.loop_depth == 0 {
+            self.error(&stmt.keyword, "break outside loop");
+        }
+    }
+
+    fn visit_continue_stmt(&mut self, stmt: &ContinueStmt) {
+        if self.loop_depth == 0 {
+            self.error(&stmt.keyword, "continue outside loop");
+        }
+    }
+
+    fn visit_return_stmt(&mut self, stmt: &ReturnStmt) {
+        if self.function == FunctionKind::None {
+            self.error(&stmt.keyword, "can't return from top-level code");
+        }
+        if let Some(value) = &stmt.value {
+            if self.function == FunctionKind::Initializer {
+                self.error(&stmt.keyword, "can't return a value from an initializer");
+            }
+            self.visit_expression(value);
+        }
+    }
+
+    fn visit_literal(&mut self, lit: &Literal) {
+        if lit.value.ttype != TokenType::Identifier {
+            return;
+        }
+        let name = lit.value.lexeme.as_str();
+        let in_own_initializer = self
+            .scopes
+            .last()
+            .and_then(|scope| scope.get(name))
+            .is_some_and(|local| !local.defined);
+        if in_own_initializer {
+            self.error(
+                &lit.value,
+                "can't read local variable in its own initializer",
+            );
+        }
+        self.resolve_local(name, &lit.binding);
+    }
+
+    fn visit_grouping(&mut self, grp: &Grouping) {
+        self.visit_expression(&grp.expr);
+    }
+
+    fn visit_unary(&mut self, unr: &Unary) {
+        self.visit_expression(&unr.expr);
+    }
+
+    fn visit_binary(&mut self, bin: &Binary) {
+        self.visit_expression(&bin.left);
+        self.visit_expression(&bin.right);
+    }
+
+    fn visit_logical(&mut self, logic: &Logical) {
+        self.visit_expression(&logic.left);
+        self.visit_expression(&logic.right);
+    }
+
+    fn visit_assign(&mut self, assign: &Assign) {
+        self.visit_expression(&assign.value);
+        self.resolve_local(assign.identifier.lexeme.as_str(), &assign.binding);
+    }
+
+    fn visit_call(&mut self, call: &Call) {
+        self.visit_expression(&call.callee);
+        for argument in call.arguments.iter() {
+            self.visit_expression(argument);
+        }
+    }
+
+    fn visit_get(&mut self, get: &Get) {
+        self.visit_expression(&get.object);
+    }
+
+    fn visit_set(&mut self, set: &Set) {
+        self.visit_expression(&set.value);
+        self.visit_expression(&set.object);
+    }
+
+    fn visit_this(&mut self, this: &This) {
+        if self.class == ClassKind::None {
+            self.error(&this.keyword, "can't use 'this' outside of a class");
+            return;
+        }
+        self.resolve_local("this", &this.binding);
+    }
+
+    fn visit_super(&mut self, sup: &Super) {
+        if self.class != ClassKind::Subclass {
+            self.error(&sup.keyword, "can't use 'super' outside of a subclass");
+            return;
+        }
+        self.resolve_local("super", &sup.binding);
+    }
+
+    fn visit_lambda(&mut self, lambda: &Lambda) {
+        self.resolve_function(&lambda.function, FunctionKind::Function);
+    }
+}
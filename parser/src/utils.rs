@@ -1,9 +1,9 @@
 use std::rc::Rc;
 
 use crate::ast::{
-    Assign, Binary, BlockStmt, BreakStmt, Call, DeclRef, DeclType, ElementType, ExprStmt,
-    Expression, FunDecl, Grouping, IfStmt, Literal, Logical, PrintStmt, ReturnStmt, StmtDecl,
-    StmtType, Unary, VarDecl, WhileStmt,
+    Assign, Binary, BlockStmt, BreakStmt, Call, ClassDecl, ContinueStmt, DeclRef, DeclType,
+    ElementType, ExprStmt, Expression, FunDecl, Get, Grouping, IfStmt, Lambda, Literal, Logical,
+    PrintStmt, ReturnStmt, Set, StmtDecl, StmtType, Super, This, Unary, VarDecl, WhileStmt,
 };
 
 // visitor trait
@@ -23,10 +23,14 @@ pub trait Visitor<Ret> {
                     .unwrap()
                     .to_owned(),
             )),
+            DeclType::Class => {
+                self.visit_class_decl(decl.as_ref().as_any().downcast_ref::<ClassDecl>().unwrap())
+            }
         }
     }
     fn visit_var_decl(&mut self, decl: &VarDecl) -> Ret;
     fn visit_fun_decl(&mut self, decl: Rc<FunDecl>) -> Ret;
+    fn visit_class_decl(&mut self, decl: &ClassDecl) -> Ret;
     fn visit_statement(&mut self, stmt: &StmtDecl) -> Ret {
         match stmt.stmt.stmt_type() {
             StmtType::Expression => {
@@ -45,6 +49,9 @@ pub trait Visitor<Ret> {
             StmtType::Break => {
                 self.visit_break_stmt(stmt.stmt.as_ref().as_any().downcast_ref().unwrap())
             }
+            StmtType::Continue => {
+                self.visit_continue_stmt(stmt.stmt.as_ref().as_any().downcast_ref().unwrap())
+            }
             StmtType::Return => {
                 self.visit_return_stmt(stmt.stmt.as_ref().as_any().downcast_ref().unwrap())
             }
@@ -58,11 +65,12 @@ pub trait Visitor<Ret> {
     fn visit_if_stmt(&mut self, stmt: &IfStmt) -> Ret;
     fn visit_while_stmt(&mut self, stmt: &WhileStmt) -> Ret;
     fn visit_break_stmt(&mut self, stmt: &BreakStmt) -> Ret;
+    fn visit_continue_stmt(&mut self, stmt: &ContinueStmt) -> Ret;
     fn visit_return_stmt(&mut self, stmt: &ReturnStmt) -> Ret;
 
     fn visit_expression(&mut self, expr: &Expression) -> Ret {
         let vall = expr.value.clone();
-        return match expr.value.as_ref().element_type() {
+        match expr.value.as_ref().element_type() {
             ElementType::Literal => {
                 self.visit_literal(vall.as_ref().as_any().downcast_ref().unwrap())
             }
@@ -80,7 +88,14 @@ pub trait Visitor<Ret> {
                 self.visit_logical(vall.as_ref().as_any().downcast_ref().unwrap())
             }
             ElementType::Call => self.visit_call(vall.as_ref().as_any().downcast_ref().unwrap()),
-        };
+            ElementType::Get => self.visit_get(vall.as_ref().as_any().downcast_ref().unwrap()),
+            ElementType::Set => self.visit_set(vall.as_ref().as_any().downcast_ref().unwrap()),
+            ElementType::This => self.visit_this(vall.as_ref().as_any().downcast_ref().unwrap()),
+            ElementType::Super => self.visit_super(vall.as_ref().as_any().downcast_ref().unwrap()),
+            ElementType::Lambda => {
+                self.visit_lambda(vall.as_ref().as_any().downcast_ref().unwrap())
+            }
+        }
     }
 
     fn visit_literal(&mut self, lit: &Literal) -> Ret;
@@ -90,6 +105,11 @@ pub trait Visitor<Ret> {
     fn visit_logical(&mut self, logic: &Logical) -> Ret;
     fn visit_assign(&mut self, assign: &Assign) -> Ret;
     fn visit_call(&mut self, call: &Call) -> Ret;
+    fn visit_get(&mut self, get: &Get) -> Ret;
+    fn visit_set(&mut self, set: &Set) -> Ret;
+    fn visit_this(&mut self, this: &This) -> Ret;
+    fn visit_super(&mut self, sup: &Super) -> Ret;
+    fn visit_lambda(&mut self, lambda: &Lambda) -> Ret;
 }
 
 pub mod expr_utils {
@@ -109,15 +129,16 @@ pub mod expr_utils {
 
     pub fn get_num_literal(num: f64) -> Expression {
         Expression {
-            value: Rc::new(Literal {
-                value: Rc::new(new_token(TokenType::Number(num))),
-            }),
+            value: Rc::new(Literal::new(Rc::new(new_token(TokenType::Number(num))))),
         }
     }
 
     pub fn group_expr(expr: Expression) -> Expression {
         Expression {
-            value: Rc::new(Grouping { expr }),
+            value: Rc::new(Grouping {
+                span: expr.span(),
+                expr,
+            }),
         }
     }
 }
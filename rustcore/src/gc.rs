@@ -0,0 +1,281 @@
+use std::{
+    cell::{Cell, Ref, RefCell, RefMut},
+    fmt,
+    mem::ManuallyDrop,
+    ops::Deref,
+    rc::Rc,
+};
+
+// Values holding `Gc` handles, and so possibly part of a reference cycle.
+pub trait Trace {
+    // reports every handle the value holds directly, once per handle. Handles held
+    // through an `Rc` that may be shared must not be reported.
+    fn trace(&self, tracer: &mut Tracer);
+}
+
+// collects the handles reported by `Trace::trace`
+pub struct Tracer {
+    // positions of the objects in the heap
+    edges: Vec<usize>,
+}
+
+impl Tracer {
+    pub fn edge<T: Trace + 'static>(&mut self, gc: &Gc<T>) {
+        self.edges.push(gc.ptr.index.get());
+    }
+}
+
+// A reference counted handle to a value on the heap of the thread. Counting frees
+// values as usual; the collector frees the cycles counting can't.
+pub struct Gc<T: Trace + 'static> {
+    ptr: Rc<GcBox<T>>,
+}
+
+// emptied when the collector frees the value, which drops the handles it holds
+struct GcBox<T> {
+    // position in the list of objects of the heap
+    index: Cell<usize>,
+    value: RefCell<Option<T>>,
+}
+
+// a value on the heap, whatever its type
+trait Object {
+    fn set_index(&self, index: usize);
+    // false when the value is borrowed mutably and can't be looked at
+    fn trace(&self, tracer: &mut Tracer) -> bool;
+    fn free(&self);
+}
+
+impl<T: Trace> Object for GcBox<T> {
+    fn set_index(&self, index: usize) {
+        self.index.set(index);
+    }
+
+    fn trace(&self, tracer: &mut Tracer) -> bool {
+        match self.value.try_borrow() {
+            Ok(value) => {
+                if let Some(value) = value.as_ref() {
+                    value.trace(tracer);
+                }
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    fn free(&self) {
+        // dropped outside of the borrow: dropping the value can free other objects
+        let value = self.value.try_borrow_mut().ok().and_then(|mut v| v.take());
+        drop(value);
+    }
+}
+
+// objects leave the heap as soon as they are dropped, so that it only lists live ones
+impl<T> Drop for GcBox<T> {
+    fn drop(&mut self) {
+        let index = self.index.get();
+        // the heap is gone already when the thread is exiting
+        let _ = HEAP.try_with(|heap| heap.unregister(index));
+    }
+}
+
+impl<T: Trace + 'static> Gc<T> {
+    pub fn new(value: T) -> Gc<T> {
+        let ptr = Rc::new(GcBox {
+            index: Cell::new(0),
+            value: RefCell::new(Some(value)),
+        });
+        let object: *const GcBox<T> = Rc::as_ptr(&ptr);
+        HEAP.with(|heap| heap.register(object));
+        Gc { ptr }
+    }
+
+    pub fn borrow(&self) -> Ref<'_, T> {
+        Ref::map(self.ptr.value.borrow(), |value| {
+            value.as_ref().expect("reachable values are never freed")
+        })
+    }
+
+    pub fn borrow_mut(&self) -> RefMut<'_, T> {
+        RefMut::map(self.ptr.value.borrow_mut(), |value| {
+            value.as_mut().expect("reachable values are never freed")
+        })
+    }
+
+    pub fn ptr_eq(this: &Gc<T>, other: &Gc<T>) -> bool {
+        Rc::ptr_eq(&this.ptr, &other.ptr)
+    }
+}
+
+impl<T: Trace> Clone for Gc<T> {
+    fn clone(&self) -> Self {
+        Self {
+            ptr: self.ptr.clone(),
+        }
+    }
+}
+
+// unchecked like `Shared`: reads don't wait for mutable borrows to end
+impl<T: Trace> Deref for Gc<T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        unsafe { self.ptr.value.as_ptr().as_ref().unwrap() }
+            .as_ref()
+            .expect("reachable values are never freed")
+    }
+}
+
+impl<T: Trace + fmt::Display> fmt::Display for Gc<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.deref())
+    }
+}
+
+impl<T: Trace + fmt::Debug> fmt::Debug for Gc<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self.deref())
+    }
+}
+
+impl<T: Trace + PartialEq> PartialEq for Gc<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.deref() == other.deref()
+    }
+}
+
+// collecting with fewer objects than this isn't worth it
+const MIN_THRESHOLD: usize = 10_000;
+
+// The values allocated on this thread. Collections start from allocations, once
+// the heap has grown to twice what survived the previous one.
+struct Heap {
+    // every live object; they remove themselves when dropped
+    objects: RefCell<Vec<*const dyn Object>>,
+    threshold: Cell<usize>,
+    stats: Cell<GcStats>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct GcStats {
+    pub collections: usize,
+    // objects still alive after the last collection
+    pub live: usize,
+    pub freed: usize,
+}
+
+thread_local!(static HEAP: Heap = Heap {
+    objects: RefCell::new(Vec::new()),
+    threshold: Cell::new(MIN_THRESHOLD),
+    stats: Cell::new(GcStats::default()),
+});
+
+impl Heap {
+    fn register(&self, object: *const dyn Object) {
+        let len = {
+            let mut objects = self.objects.borrow_mut();
+            // SAFETY: the object was just allocated
+            unsafe { (*object).set_index(objects.len()) };
+            objects.push(object);
+            objects.len()
+        };
+        if len >= self.threshold.get() {
+            self.collect();
+        }
+    }
+
+    fn unregister(&self, index: usize) {
+        let mut objects = self.objects.borrow_mut();
+        objects.swap_remove(index);
+        if let Some(moved) = objects.get(index) {
+            // SAFETY: listed objects are alive
+            unsafe { (**moved).set_index(index) };
+        }
+    }
+
+    // Mark and sweep without knowing the roots. Every strong reference to an
+    // object that isn't held by another object comes from outside of the heap: a
+    // variable of the host, a value being evaluated. Objects with such references
+    // are the roots; what they don't reach is only referenced from within cycles
+    // of garbage, which are freed by emptying their values.
+    fn collect(&self) {
+        let objects = self.objects.borrow();
+        // references from outside: all of them but those of other objects
+        let mut external: Vec<usize> = objects
+            .iter()
+            .map(|object| {
+                // SAFETY: listed objects are alive, and were allocated by an `Rc`
+                let object = ManuallyDrop::new(unsafe { Rc::from_raw(*object) });
+                Rc::strong_count(&object)
+            })
+            .collect();
+        let mut tracer = Tracer { edges: Vec::new() };
+        // the edges of object i are edges[ends[i - 1]..ends[i]]
+        let mut ends = Vec::with_capacity(objects.len());
+        let mut marked = vec![false; objects.len()];
+        for (i, object) in objects.iter().enumerate() {
+            let start = tracer.edges.len();
+            // objects being modified can't be looked at; they are kept, with
+            // everything they reference. SAFETY: listed objects are alive
+            if !unsafe { (**object).trace(&mut tracer) } {
+                marked[i] = true;
+            }
+            for child in tracer.edges[start..].iter() {
+                external[*child] = external[*child].saturating_sub(1);
+            }
+            ends.push(tracer.edges.len());
+        }
+
+        let mut pending: Vec<usize> = (0..objects.len())
+            .filter(|i| marked[*i] || external[*i] > 0)
+            .collect();
+        for i in pending.iter() {
+            marked[*i] = true;
+        }
+        while let Some(i) = pending.pop() {
+            let start = if i == 0 { 0 } else { ends[i - 1] };
+            for child in tracer.edges[start..ends[i]].iter() {
+                if !marked[*child] {
+                    marked[*child] = true;
+                    pending.push(*child);
+                }
+            }
+        }
+
+        // kept alive while freeing, as freeing drops the references between them
+        let garbage: Vec<Rc<dyn Object>> = objects
+            .iter()
+            .zip(marked)
+            .filter(|(_, marked)| !marked)
+            // SAFETY: as above
+            .map(|(object, _)| unsafe {
+                Rc::increment_strong_count(*object);
+                Rc::from_raw(*object)
+            })
+            .collect();
+        drop(objects);
+        for object in garbage.iter() {
+            object.free();
+        }
+        let freed = garbage.len();
+        drop(garbage);
+
+        let live = self.objects.borrow().len();
+        self.threshold.set(MIN_THRESHOLD.max(2 * live));
+        let mut stats = self.stats.get();
+        stats.collections += 1;
+        stats.live = live;
+        stats.freed += freed;
+        self.stats.set(stats);
+    }
+}
+
+// runs a collection now instead of waiting for the heap to grow
+pub fn collect() {
+    HEAP.with(|heap| heap.collect());
+}
+
+pub fn stats() -> GcStats {
+    HEAP.with(|heap| heap.stats.get())
+}
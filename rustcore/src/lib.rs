@@ -1,8 +1,12 @@
+pub mod gc;
+
 use std::cell::{Ref, RefCell, RefMut};
 use std::fmt;
 use std::ops::{Deref, DerefMut};
 use std::rc::Rc;
 
+pub use gc::{Gc, Trace, Tracer};
+
 pub struct Shared<T: ?Sized> {
     pub v: Rc<RefCell<T>>,
 }
@@ -26,11 +30,11 @@ impl<T: ?Sized> Shared<T> {
         Shared { v: t.clone() }
     }
 
-    pub fn borrow(&self) -> Ref<T> {
+    pub fn borrow(&self) -> Ref<'_, T> {
         self.v.borrow()
     }
 
-    pub fn borrow_mut(&self) -> RefMut<T> {
+    pub fn borrow_mut(&self) -> RefMut<'_, T> {
         self.v.borrow_mut()
     }
 
@@ -61,7 +65,7 @@ impl<T: PartialEq> PartialEq for Shared<T> {
     }
 }
 
-impl<'a, T> Deref for Shared<T> {
+impl<T> Deref for Shared<T> {
     type Target = T;
 
     #[inline]
@@ -70,7 +74,7 @@ impl<'a, T> Deref for Shared<T> {
     }
 }
 
-impl<'a, T> DerefMut for Shared<T> {
+impl<T> DerefMut for Shared<T> {
     #[inline]
     fn deref_mut(&mut self) -> &mut T {
         unsafe { self.as_ptr().as_mut().unwrap() }
@@ -0,0 +1,129 @@
+use std::fmt::Write;
+
+use crate::tokens::Span;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ColorMode {
+    Color,
+    Plain,
+}
+
+// ANSI escape sequences used in colour mode
+const RED: &str = "\x1b[1;31m";
+const BLUE: &str = "\x1b[1;34m";
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+// an error pointing at a span of the source, rendered rustc style:
+//
+// error: variable b not in scope
+//  --> input.rl:7:7
+//   |
+// 7 | print b;
+//   |       ^
+//   = note: ...
+//   at count (input.rl:7)
+//   at <script> (input.rl:12)
+#[derive(Clone, Debug)]
+pub struct Diagnostic {
+    pub message: String,
+    pub span: Span,
+    pub notes: Vec<String>,
+    // function name and line of the calls the error happened in, innermost first
+    pub backtrace: Vec<(String, u32)>,
+}
+
+impl Diagnostic {
+    pub fn new(message: &str, span: Span) -> Self {
+        Self {
+            message: message.to_string(),
+            span,
+            notes: Vec::new(),
+            backtrace: Vec::new(),
+        }
+    }
+
+    pub fn with_note(mut self, note: &str) -> Self {
+        self.notes.push(note.to_string());
+        self
+    }
+
+    pub fn with_backtrace(mut self, backtrace: Vec<(String, u32)>) -> Self {
+        self.backtrace = backtrace;
+        self
+    }
+
+    pub fn render(&self, source: &str, filename: &str, mode: ColorMode) -> String {
+        let paint = |style: &str, text: &str| match mode {
+            ColorMode::Color => format!("{}{}{}", style, text, RESET),
+            ColorMode::Plain => text.to_string(),
+        };
+
+        let line_num = self.span.line.max(1);
+        let column = self.span.column.max(1);
+        let gutter = " ".repeat(line_num.to_string().len());
+        let bar = paint(BLUE, "|");
+
+        let mut out = String::new();
+        _ = writeln!(
+            out,
+            "{}{}",
+            paint(RED, "error"),
+            paint(BOLD, format!(": {}", self.message).as_str())
+        );
+        _ = writeln!(
+            out,
+            "{}{} {}:{}:{}",
+            gutter,
+            paint(BLUE, "-->"),
+            filename,
+            line_num,
+            column
+        );
+
+        if let Some(line) = source.lines().nth(line_num as usize - 1) {
+            let line = line.trim_end_matches('\r');
+            let offset = (column as usize - 1).min(line.len());
+            // spans running past the end of the line are underlined up to its end
+            let width = self.span.len().min(line.len() - offset).max(1);
+
+            _ = writeln!(out, "{} {}", gutter, bar);
+            _ = writeln!(
+                out,
+                "{} {} {}",
+                paint(BLUE, line_num.to_string().as_str()),
+                bar,
+                line
+            );
+            _ = writeln!(
+                out,
+                "{} {} {}{}",
+                gutter,
+                bar,
+                " ".repeat(offset),
+                paint(RED, "^".repeat(width).as_str())
+            );
+        }
+
+        for note in self.notes.iter() {
+            _ = writeln!(out, "{} {} note: {}", gutter, paint(BLUE, "="), note);
+        }
+        // deep recursion repeats the same frame; longer runs of it are shown once
+        for frames in self.backtrace.chunk_by(|a, b| a == b) {
+            let shown = if frames.len() > 2 { 1 } else { frames.len() };
+            for (function, line) in &frames[..shown] {
+                _ = writeln!(out, "{} at {} ({}:{})", gutter, function, filename, line);
+            }
+            if shown < frames.len() {
+                let more = frames.len() - shown;
+                _ = writeln!(
+                    out,
+                    "{} ... {} more frames in {}",
+                    gutter, more, frames[0].0
+                );
+            }
+        }
+
+        out
+    }
+}
@@ -0,0 +1,30 @@
+use std::fmt::Display;
+
+use crate::{diagnostics::Diagnostic, tokens::Span};
+
+#[derive(Clone, Debug)]
+pub struct ScanError {
+    pub message: String,
+    pub span: Span,
+}
+
+impl ScanError {
+    pub fn new(message: &str, span: Span) -> Self {
+        Self {
+            message: message.to_string(),
+            span,
+        }
+    }
+
+    pub fn diagnostic(&self) -> Diagnostic {
+        Diagnostic::new(&self.message, self.span)
+    }
+}
+
+impl Display for ScanError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[line {}] Error: {}", self.span.line, self.message)
+    }
+}
+
+impl std::error::Error for ScanError {}
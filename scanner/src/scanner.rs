@@ -1,6 +1,9 @@
+pub mod diagnostics;
+pub mod error;
 pub mod tokens;
 
-use tokens::{get_reserved_keyword, Token, TokenType};
+use error::ScanError;
+use tokens::{get_reserved_keyword, Span, Token, TokenType};
 
 use std::io::{prelude::*, ErrorKind, SeekFrom};
 use std::iter::Iterator;
@@ -11,33 +14,33 @@ where
     R: Read + Seek,
 {
     contents: R,
-    start: u32,
-    current: u32,
+    // byte offsets of the start of the token being scanned and of the next unread byte
+    start: usize,
+    current: usize,
     line: u32,
+    // byte offset of the first byte of the current line, used to compute columns
+    line_start: usize,
+    // position of the token being scanned
+    start_line: u32,
+    start_column: u32,
     end_reached: bool,
 }
 
 impl<R: Read + Seek> Iterator for Scanner<R> {
-    type Item = Result<Token, String>;
+    type Item = Result<Token, ScanError>;
 
     fn next(&mut self) -> Option<Self::Item> {
         if self.end_reached {
             return None;
         }
-        let result = self.scan_token();
-        if let Ok(maybe_token) = result {
-            if maybe_token.is_none() {
+        match self.scan_token() {
+            Ok(Some(token)) => Some(Ok(token)),
+            Ok(None) => {
                 self.end_reached = true;
-                Some(Ok(Token {
-                    ttype: TokenType::Eof,
-                    lexeme: String::from(""),
-                    line_num: self.line,
-                }))
-            } else {
-                Some(Ok(maybe_token.unwrap()))
+                self.mark_start();
+                Some(Ok(self.token(TokenType::Eof, String::from(""))))
             }
-        } else {
-            Some(Err(result.unwrap_err()))
+            Err(err) => Some(Err(err)),
         }
     }
 }
@@ -50,11 +53,15 @@ impl<R: Read + Seek> Scanner<R> {
             start: 0,
             current: 0,
             line: 1,
+            line_start: 0,
+            start_line: 1,
+            start_column: 1,
             end_reached: false,
         }
     }
 
-    fn scan_token(&mut self) -> Result<Option<Token>, String> {
+    fn scan_token(&mut self) -> Result<Option<Token>, ScanError> {
+        self.mark_start();
         let next_char = self.advance(); // not caring a lot about unicodes here!!
         if next_char.is_none() {
             return Ok(None);
@@ -63,56 +70,16 @@ impl<R: Read + Seek> Scanner<R> {
         let next_char = String::from(next_char.unwrap());
 
         let token = match next_char.as_str() {
-            ";" => Token {
-                ttype: TokenType::Semicolon,
-                lexeme: next_char.to_string(),
-                line_num: self.line,
-            },
-            "," => Token {
-                ttype: TokenType::Comma,
-                lexeme: next_char.to_string(),
-                line_num: self.line,
-            },
-            "." => Token {
-                ttype: TokenType::Dot,
-                lexeme: next_char.to_string(),
-                line_num: self.line,
-            },
-            "(" => Token {
-                ttype: TokenType::LeftBrace,
-                lexeme: next_char.to_string(),
-                line_num: self.line,
-            },
-            ")" => Token {
-                ttype: TokenType::RightBrace,
-                lexeme: next_char.to_string(),
-                line_num: self.line,
-            },
-            "{" => Token {
-                ttype: TokenType::LeftParen,
-                lexeme: next_char.to_string(),
-                line_num: self.line,
-            },
-            "}" => Token {
-                ttype: TokenType::RightParen,
-                lexeme: next_char.to_string(),
-                line_num: self.line,
-            },
-            "-" => Token {
-                ttype: TokenType::Minus,
-                lexeme: next_char.to_string(),
-                line_num: self.line,
-            },
-            "+" => Token {
-                ttype: TokenType::Plus,
-                lexeme: next_char.to_string(),
-                line_num: self.line,
-            },
-            "*" => Token {
-                ttype: TokenType::Star,
-                lexeme: next_char.to_string(),
-                line_num: self.line,
-            },
+            ";" => self.token(TokenType::Semicolon, next_char.to_string()),
+            "," => self.token(TokenType::Comma, next_char.to_string()),
+            "." => self.token(TokenType::Dot, next_char.to_string()),
+            "(" => self.token(TokenType::LeftBrace, next_char.to_string()),
+            ")" => self.token(TokenType::RightBrace, next_char.to_string()),
+            "{" => self.token(TokenType::LeftParen, next_char.to_string()),
+            "}" => self.token(TokenType::RightParen, next_char.to_string()),
+            "-" => self.token(TokenType::Minus, next_char.to_string()),
+            "+" => self.token(TokenType::Plus, next_char.to_string()),
+            "*" => self.token(TokenType::Star, next_char.to_string()),
             "!" => {
                 let is_bang_equal = self.match_curr("=");
                 let (token_type, lexeme) = if is_bang_equal {
@@ -121,11 +88,7 @@ impl<R: Read + Seek> Scanner<R> {
                 } else {
                     (TokenType::Bang, "!")
                 };
-                Token {
-                    ttype: token_type,
-                    lexeme: lexeme.to_string(),
-                    line_num: self.line,
-                }
+                self.token(token_type, lexeme.to_string())
             }
 
             "=" => {
@@ -136,11 +99,7 @@ impl<R: Read + Seek> Scanner<R> {
                 } else {
                     (TokenType::Equal, "=")
                 };
-                Token {
-                    ttype: token_type,
-                    lexeme: lexeme.to_string(),
-                    line_num: self.line,
-                }
+                self.token(token_type, lexeme.to_string())
             }
 
             "<" => {
@@ -151,11 +110,7 @@ impl<R: Read + Seek> Scanner<R> {
                 } else {
                     (TokenType::Less, "<")
                 };
-                Token {
-                    ttype: token_type,
-                    lexeme: lexeme.to_string(),
-                    line_num: self.line,
-                }
+                self.token(token_type, lexeme.to_string())
             }
 
             ">" => {
@@ -166,11 +121,7 @@ impl<R: Read + Seek> Scanner<R> {
                 } else {
                     (TokenType::Greater, ">")
                 };
-                Token {
-                    ttype: token_type,
-                    lexeme: lexeme.to_string(),
-                    line_num: self.line,
-                }
+                self.token(token_type, lexeme.to_string())
             }
 
             "/" => {
@@ -191,21 +142,14 @@ impl<R: Read + Seek> Scanner<R> {
                     return self.scan_token();
                 }
 
-                Token {
-                    ttype: TokenType::Slash,
-                    lexeme: "/".to_string(),
-                    line_num: self.line,
-                }
+                self.token(TokenType::Slash, "/".to_string())
             }
 
             "\"" => {
                 let token = self.extract_string_token();
                 if let Ok(contents) = token {
-                    Token {
-                        ttype: TokenType::String(contents),
-                        lexeme: String::new(),
-                        line_num: self.line,
-                    }
+                    let lexeme = format!("\"{}\"", contents);
+                    self.token(TokenType::String(contents), lexeme)
                 } else {
                     return Err(token.err().unwrap());
                 }
@@ -214,11 +158,7 @@ impl<R: Read + Seek> Scanner<R> {
             d if d.chars().collect::<Vec<char>>()[0].is_numeric() => {
                 let token = self.extract_number(d);
                 if let Ok(contents) = token {
-                    Token {
-                        ttype: TokenType::Number(contents),
-                        lexeme: String::new(),
-                        line_num: self.line,
-                    }
+                    contents
                 } else {
                     return Err(token.err().unwrap());
                 }
@@ -239,9 +179,7 @@ impl<R: Read + Seek> Scanner<R> {
 
             _ => {
                 // unhandled
-                let msg = format!("error scanning token: {}", next_char);
-                println!("{}", msg);
-                return Err(msg);
+                return Err(self.error(format!("unexpected character '{}'", next_char).as_str()));
             }
         };
 
@@ -250,9 +188,7 @@ impl<R: Read + Seek> Scanner<R> {
 
     fn advance(&mut self) -> Option<char> {
         let mut buf: [u8; 1] = [0; 1];
-        let result = self.contents.read_exact(&mut buf);
-        if result.is_err() {
-            let err = result.unwrap_err();
+        if let Err(err) = self.contents.read_exact(&mut buf) {
             if err.kind() != ErrorKind::UnexpectedEof {
                 println!("the error is: {}", err);
             }
@@ -260,12 +196,43 @@ impl<R: Read + Seek> Scanner<R> {
             return None;
         }
         let c = buf[0] as char;
+        self.current += 1;
         if c == '\n' {
             self.line += 1;
+            self.line_start = self.current;
         }
         Some(c)
     }
 
+    // text scanned since the start of the current token
+    fn current_span(&self) -> Span {
+        Span {
+            start: self.start,
+            end: self.current,
+            line: self.start_line,
+            column: self.start_column,
+        }
+    }
+
+    fn error(&self, msg: &str) -> ScanError {
+        ScanError::new(msg, self.current_span())
+    }
+
+    fn mark_start(&mut self) {
+        self.start = self.current;
+        self.start_line = self.line;
+        self.start_column = (self.current - self.line_start) as u32 + 1;
+    }
+
+    fn token(&self, ttype: TokenType, lexeme: String) -> Token {
+        Token {
+            ttype,
+            lexeme,
+            line_num: self.start_line,
+            span: self.current_span(),
+        }
+    }
+
     fn match_curr(&mut self, value: &str) -> bool {
         let next_c = self.peek();
         next_c.is_some() && next_c.unwrap().to_string() == value
@@ -294,7 +261,7 @@ impl<R: Read + Seek> Scanner<R> {
         Some(buf[0] as char)
     }
 
-    fn extract_string_token(&mut self) -> Result<String, String> {
+    fn extract_string_token(&mut self) -> Result<String, ScanError> {
         let mut string_content = String::new();
         // TODO: the extract* methods seem to have a common pattern of peek/advance, filter, accumulate etc. Might
         // have a lambda accepting function here.
@@ -302,7 +269,7 @@ impl<R: Read + Seek> Scanner<R> {
             let c = self.advance();
 
             if c.is_none() {
-                return Err("unterminated string".to_string());
+                return Err(self.error("unterminated string"));
             }
             let c = c.unwrap();
             if c == '"' {
@@ -312,7 +279,7 @@ impl<R: Read + Seek> Scanner<R> {
         }
     }
 
-    fn extract_number(&mut self, start: &str) -> Result<f64, String> {
+    fn extract_number(&mut self, start: &str) -> Result<Token, ScanError> {
         let mut content = String::new();
         content.push_str(start);
 
@@ -333,13 +300,13 @@ impl<R: Read + Seek> Scanner<R> {
 
         let num = content.parse::<f64>();
         if let Ok(num) = num {
-            Ok(num)
+            Ok(self.token(TokenType::Number(num), content))
         } else {
-            Err(num.err().unwrap().to_string())
+            Err(self.error(num.err().unwrap().to_string().as_str()))
         }
     }
 
-    fn extract_identifier(&mut self, start: &str) -> Result<Token, String> {
+    fn extract_identifier(&mut self, start: &str) -> Result<Token, ScanError> {
         let mut content = String::new();
         content.push_str(start);
 
@@ -358,11 +325,7 @@ impl<R: Read + Seek> Scanner<R> {
             content.push_str(next_c.to_string().as_str());
         }
 
-        let mut token = Token {
-            ttype: TokenType::Identifier,
-            lexeme: content.clone(),
-            line_num: self.line,
-        };
+        let mut token = self.token(TokenType::Identifier, content.clone());
 
         if let Some(keyword) = get_reserved_keyword(content.as_str()) {
             token.ttype = keyword;
@@ -51,15 +51,52 @@ pub enum TokenType {
     Var,
     While,
     Break,
+    Continue,
 
     Eof,
 }
 
+// location of a piece of source text: the byte range [start, end) and the
+// line/column (both 1-based) where it begins
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: u32,
+    pub column: u32,
+}
+
+impl Span {
+    // smallest span covering both self and other
+    pub fn to(&self, other: Span) -> Span {
+        let (first, last) = if self.start <= other.start {
+            (*self, other)
+        } else {
+            (other, *self)
+        };
+        Span {
+            start: first.start,
+            end: first.end.max(last.end),
+            line: first.line,
+            column: first.column,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+}
+
 #[derive(Debug)]
 pub struct Token {
     pub ttype: TokenType,
     pub lexeme: String,
     pub line_num: u32,
+    pub span: Span,
 }
 
 pub type TokenRef = Rc<Token>;
@@ -82,6 +119,7 @@ static RESERVED_KEYWORDS: phf::Map<&'static str, TokenType> = phf_map! {
     "var" => TokenType::Var,
     "while" => TokenType::While,
     "break" => TokenType::Break,
+    "continue" => TokenType::Continue,
 };
 
 pub fn get_reserved_keyword(keyword: &str) -> Option<TokenType> {
@@ -93,5 +131,6 @@ pub fn new_token(ttype: TokenType) -> Token {
         ttype,
         lexeme: "".to_string(),
         line_num: 0,
+        span: Span::default(),
     }
 }
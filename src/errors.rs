@@ -1,16 +1,26 @@
 #[allow(dead_code)]
 pub mod error_handling {
+    use std::io::{self, IsTerminal};
+
+    use scanner::diagnostics::{ColorMode, Diagnostic};
+
     pub struct ErrorState {
         pub error_occured: bool,
     }
 
     impl ErrorState {
-        pub fn error(&self, line: u32, msg: &str) {
-            self.report(line, "", msg);
+        pub fn report(&mut self, diagnostic: &Diagnostic, source: &str, filename: &str) {
+            self.error_occured = true;
+            eprintln!("{}", diagnostic.render(source, filename, color_mode()));
         }
+    }
 
-        fn report(&self, line: u32, location: &str, msg: &str) {
-            println!("[line: {}, where: {}] Error: {} ", line, location, msg);
+    // colour is only used when writing to a terminal and NO_COLOR isn't set
+    fn color_mode() -> ColorMode {
+        if std::env::var_os("NO_COLOR").is_none() && io::stderr().is_terminal() {
+            ColorMode::Color
+        } else {
+            ColorMode::Plain
         }
     }
 }
@@ -0,0 +1,267 @@
+// Embedding API: runs Lox source from a Rust host.
+//
+//     let mut lox = Lox::new();
+//     lox.set_global("limit", 10.0);
+//     lox.eval("fun double(n) { return n * 2; }")?;
+//     let result = lox.call_function("double", vec![IResult::Number(21.0)])?;
+use std::{
+    fmt::Display,
+    fs,
+    io::{self, Cursor, Stdout, Write},
+    path::Path,
+    rc::Rc,
+};
+
+use interpreter::{
+    error::{RuntimeError, RuntimeErrorKind},
+    Interpreter,
+};
+use parser::{ast::DeclRef, resolver::Resolver, Parser};
+use rustcore::Shared;
+use scanner::{diagnostics::Diagnostic, Scanner};
+use vm::{
+    compiler::Compiler,
+    disasm::Disassembler,
+    serialize,
+    value::{Function, Value},
+    Vm,
+};
+
+pub use interpreter::{
+    convert::{FromLox, IntoLox},
+    foreignf::NativeRegistry,
+    interrupt::InterruptHandle,
+    limits::Limits,
+    result::IResult,
+};
+pub use vm::serialize::LoadError;
+
+#[derive(Debug)]
+pub enum LoxError {
+    Io(io::Error),
+    // scan, parse and resolve errors; nothing was run
+    Syntax(Vec<Diagnostic>),
+    Runtime(RuntimeError),
+    // a compiled script that can't be loaded; nothing was run
+    Load(LoadError),
+    // the script called exit with this status; the process keeps running
+    Exit(i32),
+}
+
+impl LoxError {
+    // the errors pointing at the source, ready to be rendered
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        match self {
+            LoxError::Io(err) => vec![Diagnostic::new(&err.to_string(), Default::default())],
+            LoxError::Syntax(diagnostics) => diagnostics.clone(),
+            LoxError::Runtime(err) => vec![err.diagnostic()],
+            LoxError::Load(err) => vec![Diagnostic::new(&err.to_string(), Default::default())],
+            LoxError::Exit(_) => Vec::new(),
+        }
+    }
+}
+
+impl Display for LoxError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LoxError::Io(err) => write!(f, "{}", err),
+            LoxError::Syntax(diagnostics) => {
+                let messages: Vec<&str> = diagnostics.iter().map(|d| d.message.as_str()).collect();
+                write!(f, "{}", messages.join("\n"))
+            }
+            LoxError::Runtime(err) => write!(f, "{}", err.message),
+            LoxError::Load(err) => write!(f, "{}", err),
+            LoxError::Exit(code) => write!(f, "script exited with status {}", code),
+        }
+    }
+}
+
+impl std::error::Error for LoxError {}
+
+impl From<RuntimeError> for LoxError {
+    fn from(err: RuntimeError) -> Self {
+        match err.kind {
+            RuntimeErrorKind::Exit(code) => LoxError::Exit(code),
+            _ => LoxError::Runtime(err),
+        }
+    }
+}
+
+// How scripts are run: walking the syntax tree, or compiled to bytecode for the
+// stack vm. Both give the same output and errors.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Backend {
+    #[default]
+    TreeWalker,
+    Vm,
+}
+
+enum Engine<W: Write> {
+    TreeWalker(Interpreter<W>),
+    Vm(Vm<W>),
+}
+
+// An interpreter together with the pipeline feeding it. Globals defined by one
+// call to `eval` stay visible to the following ones.
+pub struct Lox<W: Write = Stdout> {
+    engine: Engine<W>,
+}
+
+impl Default for Lox<Stdout> {
+    fn default() -> Self {
+        Lox::new()
+    }
+}
+
+impl Lox<Stdout> {
+    pub fn new() -> Self {
+        Lox::with_output(Shared::new(io::stdout()))
+    }
+}
+
+impl<W: Write> Lox<W> {
+    // `print` writes to the given output instead of stdout
+    pub fn with_output(output: Shared<W>) -> Self {
+        Lox::with_natives(output, NativeRegistry::default())
+    }
+
+    pub fn with_natives(output: Shared<W>, natives: NativeRegistry) -> Self {
+        Lox::with_backend(output, natives, Backend::TreeWalker)
+    }
+
+    pub fn with_backend(output: Shared<W>, natives: NativeRegistry, backend: Backend) -> Self {
+        let engine = match backend {
+            Backend::TreeWalker => {
+                Engine::TreeWalker(Interpreter::new_with_registry(output, natives))
+            }
+            Backend::Vm => Engine::Vm(Vm::new_with_registry(output, natives)),
+        };
+        Lox { engine }
+    }
+
+    // runs the source; the result is the value of the last statement when it is an
+    // expression statement, IResult::None otherwise
+    pub fn eval(&mut self, source: &str) -> Result<IResult, LoxError> {
+        let decls = parse(source)?;
+        match &mut self.engine {
+            Engine::TreeWalker(interpreter) => Ok(interpreter.interpret(decls)?),
+            Engine::Vm(vm) => Ok(vm.interpret(compile(&decls)?)?.into_lox()),
+        }
+    }
+
+    // runs a compiled script; see `compile_bytecode`
+    pub fn run_bytecode(&mut self, bytes: &[u8]) -> Result<IResult, LoxError> {
+        let script = serialize::deserialize(bytes).map_err(LoxError::Load)?;
+        match &mut self.engine {
+            Engine::Vm(vm) => Ok(vm.interpret(script)?.into_lox()),
+            Engine::TreeWalker(_) => Err(LoxError::Io(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "compiled scripts only run on the vm backend",
+            ))),
+        }
+    }
+
+    // runs a source file, or a compiled script
+    pub fn run_file(&mut self, path: impl AsRef<Path>) -> Result<IResult, LoxError> {
+        let bytes = fs::read(path).map_err(LoxError::Io)?;
+        if serialize::is_bytecode(&bytes) {
+            return self.run_bytecode(&bytes);
+        }
+        let source = String::from_utf8(bytes)
+            .map_err(|err| LoxError::Io(io::Error::new(io::ErrorKind::InvalidData, err)))?;
+        self.eval(&source)
+    }
+
+    // bounds on what the following runs may use; exceeding one stops the run with a
+    // RuntimeErrorKind::ResourceLimitExceeded error
+    pub fn set_limits(&mut self, limits: Limits) {
+        match &mut self.engine {
+            Engine::TreeWalker(interpreter) => interpreter.set_limits(limits),
+            Engine::Vm(vm) => vm.set_limits(limits),
+        }
+    }
+
+    // stops the running script from another thread with a
+    // RuntimeErrorKind::Interrupted error; take it before starting the run
+    pub fn interrupt_handle(&self) -> InterruptHandle {
+        match &self.engine {
+            Engine::TreeWalker(interpreter) => interpreter.interrupt_handle(),
+            Engine::Vm(vm) => vm.interrupt_handle(),
+        }
+    }
+
+    pub fn set_global(&mut self, name: &str, value: impl IntoLox) {
+        let value = value.into_lox();
+        match &mut self.engine {
+            Engine::TreeWalker(interpreter) => interpreter.set_global(name, value),
+            Engine::Vm(vm) => vm.set_global(name, Value::from(value)),
+        }
+    }
+
+    pub fn get_global(&self, name: &str) -> Option<IResult> {
+        match &self.engine {
+            Engine::TreeWalker(interpreter) => interpreter.get_global(name),
+            Engine::Vm(vm) => vm.get_global(name).map(Value::into_lox),
+        }
+    }
+
+    // calls a global function or class with the given arguments
+    pub fn call_function(&mut self, name: &str, args: Vec<IResult>) -> Result<IResult, LoxError> {
+        let Some(callee) = self.get_global(name) else {
+            return Err(RuntimeError::native(
+                RuntimeErrorKind::UndefinedVariable,
+                format!("variable {} not in scope", name).as_str(),
+            )
+            .into());
+        };
+        match &mut self.engine {
+            Engine::TreeWalker(interpreter) => Ok(interpreter.call(callee, args)?),
+            Engine::Vm(vm) => {
+                let args = args.into_iter().map(Value::from).collect();
+                Ok(vm.call(Value::from(callee), args)?.into_lox())
+            }
+        }
+    }
+}
+
+// the bytecode the vm runs for the source, as text; see `vm::disasm`
+pub fn disassemble(source: &str) -> Result<String, LoxError> {
+    let script = compile(&parse(source)?)?;
+    Ok(Disassembler::disassemble(&script))
+}
+
+// the source compiled for the vm, to be saved and run later without the source
+pub fn compile_bytecode(source: &str) -> Result<Vec<u8>, LoxError> {
+    let script = compile(&parse(source)?)?;
+    Ok(serialize::serialize(&script))
+}
+
+// whether the bytes are a compiled script rather than source
+pub fn is_bytecode(bytes: &[u8]) -> bool {
+    serialize::is_bytecode(bytes)
+}
+
+// scans, parses and resolves the source
+fn parse(source: &str) -> Result<Vec<DeclRef>, LoxError> {
+    let mut tokens = Vec::new();
+    for token in Scanner::build_scanner(Cursor::new(source.as_bytes())) {
+        match token {
+            Ok(token) => tokens.push(Rc::new(token)),
+            Err(err) => return Err(LoxError::Syntax(vec![err.diagnostic()])),
+        }
+    }
+
+    let decls = Parser::new(tokens)
+        .parse()
+        .map_err(|errors| LoxError::Syntax(errors.iter().map(|err| err.diagnostic()).collect()))?;
+    Resolver::new()
+        .resolve(&decls)
+        .map_err(|errors| LoxError::Syntax(errors.iter().map(|err| err.diagnostic()).collect()))?;
+    Ok(decls)
+}
+
+fn compile(decls: &[DeclRef]) -> Result<Rc<Function>, LoxError> {
+    Compiler::new()
+        .compile(decls)
+        .map_err(|errors| LoxError::Syntax(errors.iter().map(|err| err.diagnostic()).collect()))
+}
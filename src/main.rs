@@ -1,46 +1,64 @@
 mod errors;
+#[cfg(test)]
 mod tests;
 
 use std::{
     cell::RefCell,
-    env,
-    fs::File,
-    io::{self, BufRead, BufReader, Cursor, Read, Seek, Stdout, Write},
+    env, fs,
+    io::{self, BufRead, Stdout, Write},
     process::exit,
-    rc::Rc,
 };
 
-use parser::{ast::DeclRef, printer::AstPrinter, utils::Visitor};
-
-use scanner::tokens::TokenRef;
-
 use errors::error_handling::ErrorState;
-use interpreter::Interpreter;
-
-use parser::Parser;
-
-use scanner::Scanner;
+use rlox::{Backend, IResult, Lox, LoxError, NativeRegistry};
+use rustcore::Shared;
+use scanner::diagnostics::Diagnostic;
 
-thread_local!(static ERROR_STATE: RefCell<ErrorState>  = RefCell::new(ErrorState { error_occured: false }));
+thread_local!(static ERROR_STATE: RefCell<ErrorState>  = const { RefCell::new(ErrorState { error_occured: false }) });
 
 fn main() {
     //try_ast_printer();
-    let args: Vec<String> = env::args().collect();
-
-    if args.len() == 1 {
+    let mut args: Vec<String> = env::args().collect();
+    // `--vm` runs scripts on the bytecode vm instead of the tree-walker
+    let backend = match args.iter().position(|arg| arg == "--vm") {
+        Some(index) => {
+            args.remove(index);
+            Backend::Vm
+        }
+        None => Backend::TreeWalker,
+    };
+
+    if args.len() == 3 && args[1] == "disasm" {
+        disassemble_file(&args[2]);
+        exit_if_error();
+    } else if args.len() == 5 && args[1] == "compile" && args[3] == "-o" {
+        compile_file(&args[2], &args[4]);
+        exit_if_error();
+    } else if args.len() == 1 {
         // start the interpreter mode
-        run_prompt();
+        run_prompt(backend);
     } else if args.len() == 2 {
         let filename = &args[1];
-        run_file(filename);
+        run_file(filename, backend);
+        exit_if_error();
     } else {
-        eprintln!("Usage: rlox [filename]");
+        eprintln!(
+            "Usage: rlox [--vm] [filename]\n       rlox disasm <filename>\n       rlox compile <filename> -o <output>"
+        );
     }
 }
 
+fn new_lox(backend: Backend) -> Lox<Stdout> {
+    Lox::with_backend(
+        Shared::new(io::stdout()),
+        NativeRegistry::default(),
+        backend,
+    )
+}
+
 #[allow(dead_code)]
-fn run_prompt() {
-    let mut interpreter = Interpreter::default();
+fn run_prompt(backend: Backend) {
+    let mut lox = new_lox(backend);
     loop {
         // simply moving this line outside the loop will append to this "line" variable and not just store the current input
         let mut line = String::new();
@@ -54,68 +72,99 @@ fn run_prompt() {
         if line.trim().is_empty() {
             continue;
         }
-        run_line(&mut interpreter, &line);
+        run_line(&mut lox, &line);
         set_error(false);
     }
 }
 
 #[allow(dead_code)]
-fn run_file(filename: &str) {
-    let mut interpreter = Interpreter::default();
-    let scanner = Scanner::build_scanner(BufReader::new(File::open(filename).unwrap()));
-    _ = execute(&mut interpreter, scanner);
-}
+fn run_file(filename: &str, backend: Backend) {
+    let bytes = match fs::read(filename) {
+        Ok(bytes) => bytes,
+        Err(err) => {
+            eprintln!("can't read {}: {}", filename, err);
+            exit(66);
+        }
+    };
+    // compiled scripts come without their source and only run on the vm
+    if rlox::is_bytecode(&bytes) {
+        if let Err(err) = new_lox(Backend::Vm).run_bytecode(&bytes) {
+            report_error(&err, "", filename);
+        }
+        return;
+    }
 
-fn run_line(interpreter: &mut Interpreter<Stdout>, contents: &str) {
-    let cursor = Cursor::new(contents.as_bytes());
-    let scanner = Scanner::build_scanner(BufReader::new(cursor));
-    _ = execute(interpreter, scanner);
+    let source = match String::from_utf8(bytes) {
+        Ok(source) => source,
+        Err(err) => {
+            eprintln!("can't read {}: {}", filename, err);
+            exit(66);
+        }
+    };
+    if let Err(err) = new_lox(backend).eval(&source) {
+        report_error(&err, &source, filename);
+    }
 }
 
-fn execute<T: Read + Seek, I: Write>(
-    interpreter: &mut Interpreter<I>,
-    scanner: Scanner<T>,
-) -> Result<(), String> {
-    let mut tokens = Vec::new();
-    for lexeme in scanner {
-        if lexeme.is_err() {
-            eprintln!("error in input");
-            return Err(lexeme.unwrap_err());
+// saves the bytecode of the file, to be run later with `rlox <output>`
+fn compile_file(filename: &str, output: &str) {
+    let source = read_source(filename);
+    match rlox::compile_bytecode(&source) {
+        Ok(bytes) => {
+            if let Err(err) = fs::write(output, bytes) {
+                eprintln!("can't write {}: {}", output, err);
+                exit(73);
+            }
         }
-        tokens.push(Rc::new(lexeme.ok().unwrap()));
-        println!("{:?}", tokens.last().unwrap());
+        Err(err) => report(&err.diagnostics(), &source, filename),
     }
-    if let Some(decls) = parse_tokens(tokens) {
-        // // print parser output
-        for decl in decls.iter() {
-            AstPrinter {}.visit_declaration(decl.clone());
-        }
+}
 
-        let result = interpreter.interpret(decls);
-        match result {
-            Ok(result) => {
-                println!("{}", result);
-                Ok(())
-            }
-            Err(msg) => {
-                //eprintln!("{}", msg)
-                Err(msg)
-            }
+// prints the bytecode the vm would run for the file
+fn disassemble_file(filename: &str) {
+    let source = read_source(filename);
+    match rlox::disassemble(&source) {
+        Ok(listing) => print!("{}", listing),
+        Err(err) => report(&err.diagnostics(), &source, filename),
+    }
+}
+
+fn run_line(lox: &mut Lox<Stdout>, contents: &str) {
+    match lox.eval(contents) {
+        // echo the value of expressions typed at the prompt
+        Ok(IResult::None) => {}
+        Ok(value) => println!("{}", value),
+        Err(err) => report_error(&err, contents, "<stdin>"),
+    }
+}
+
+fn read_source(filename: &str) -> String {
+    match fs::read_to_string(filename) {
+        Ok(source) => source,
+        Err(err) => {
+            eprintln!("can't read {}: {}", filename, err);
+            exit(66);
         }
-    } else {
-        Err("error in parsing tokens".to_string())
     }
 }
 
-fn parse_tokens(tokens: Vec<TokenRef>) -> Option<Vec<DeclRef>> {
-    let mut parser = Parser::new(tokens);
-    match parser.parse() {
-        Ok(result) => Some(result),
-        Err(_) => None,
+// scripts calling exit end the process with their status
+fn report_error(err: &LoxError, source: &str, filename: &str) {
+    if let LoxError::Exit(code) = err {
+        exit(*code);
     }
+    report(&err.diagnostics(), source, filename);
 }
 
-#[allow(dead_code)]
+fn report(diagnostics: &[Diagnostic], source: &str, filename: &str) {
+    ERROR_STATE.with(|val| {
+        for diagnostic in diagnostics {
+            val.borrow_mut().report(diagnostic, source, filename);
+        }
+    })
+}
+
+// scripts that failed to compile or run end the process with a non-zero status
 fn exit_if_error() {
     ERROR_STATE.with(|val| {
         if val.borrow().error_occured {
@@ -1,16 +1,48 @@
 use std::{
-    fs::{self, File},
-    io::{BufReader, Cursor, Read, Seek},
+    cell::OnceCell,
+    fs,
+    io::{Cursor, Read, Seek},
+    rc::Rc,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::Duration,
 };
 
-use interpreter::Interpreter;
-use rustcore::Shared;
-use scanner::Scanner;
+use interpreter::{
+    callable::LoxCallable,
+    error::{RuntimeError, RuntimeErrorKind},
+    foreignf::NativeRegistry,
+    limits::Limits,
+    result::IResult,
+    Interpreter,
+};
+use parser::{
+    ast::{PrintStmt, StmtDecl},
+    Parser,
+};
+use rlox::{Backend, InterruptHandle, LoadError, Lox, LoxError};
+use rustcore::{gc, Shared};
+use scanner::{
+    diagnostics::ColorMode,
+    tokens::{Span, TokenRef, TokenType},
+    Scanner,
+};
+use vm::{
+    chunk::{Chunk, OpCode},
+    serialize,
+    value::{Function, Value},
+};
 
 struct RunParams {
     should_fail: bool,
 }
 
+// every script is run on both backends, which must agree
+const BACKENDS: [Backend; 2] = [Backend::TreeWalker, Backend::Vm];
+
 #[test]
 fn successful_interpreter_runs() {
     let params = &RunParams { should_fail: false };
@@ -26,31 +58,41 @@ fn out_of_scope_err() {
 
 #[test]
 fn if_tests() {
-    let mut params = &mut RunParams { should_fail: false };
+    let params = &RunParams { should_fail: false };
     compare_interpreter_runs("data/4/input.rl", "data/4/expected.txt", params);
-
-    params.should_fail = true;
-    compare_interpreter_runs("data/4/input_err.rl", "data/4/expected_err.txt", params);
+    compare_interpreter_runs(
+        "data/4/input_truthy.rl",
+        "data/4/expected_truthy.txt",
+        params,
+    );
 }
 
 #[test]
 fn logical_operators() {
     let params = &RunParams { should_fail: false };
     compare_interpreter_runs("data/5/input.rl", "data/5/expected.txt", params);
+    compare_interpreter_runs(
+        "data/5/input_values.rl",
+        "data/5/expected_values.txt",
+        params,
+    );
 }
 
 #[test]
 fn while_loop() {
-    let mut params = &mut RunParams { should_fail: false };
+    let params = &mut RunParams { should_fail: false };
     compare_interpreter_runs("data/6/input.rl", "data/6/expected.txt", params);
 
+    // far more iterations than the native stack would hold if each of them recursed
+    compare_interpreter_runs("data/6/input_long.rl", "data/6/expected_long.txt", params);
+
     params.should_fail = true;
     compare_interpreter_runs("data/6/input_err.rl", "data/6/expected_err.txt", params);
 }
 
 #[test]
 fn test_break() {
-    let mut params = &mut RunParams { should_fail: false };
+    let params = &mut RunParams { should_fail: false };
     compare_interpreter_runs("data/7/input.rl", "data/7/expected.txt", params);
     compare_interpreter_runs(
         "data/7/input_nested.rl",
@@ -62,6 +104,32 @@ fn test_break() {
     compare_interpreter_runs("data/7/input_err.rl", "data/7/expected_err.txt", params);
 }
 
+#[test]
+fn test_continue() {
+    let params = &mut RunParams { should_fail: false };
+    compare_interpreter_runs(
+        "data/7/input_continue.rl",
+        "data/7/expected_continue.txt",
+        params,
+    );
+
+    params.should_fail = true;
+    compare_interpreter_runs(
+        "data/7/input_continue_err.rl",
+        "data/7/expected_continue_err.txt",
+        params,
+    );
+}
+
+#[test]
+fn for_loop() {
+    let params = &mut RunParams { should_fail: false };
+    compare_interpreter_runs("data/19/input.rl", "data/19/expected.txt", params);
+
+    params.should_fail = true;
+    compare_interpreter_runs("data/19/input_err.rl", "data/19/expected_err.txt", params);
+}
+
 #[test]
 fn functions() {
     let params = &RunParams { should_fail: false };
@@ -73,59 +141,733 @@ fn functions() {
     );
 }
 
-// #[test]
-// fn recursion() {
-//     let params = &RunParams { should_fail: false };
-//     compare_interpreter_runs("data/8/input.rl", "data/8/expected.txt", params);
-// }
-
-// #[test]
-// fn class() {
-//     let params = &RunParams { should_fail: false };
-//     compare_interpreter_runs("data/9/input.rl", "data/9/expected.txt", params);
-// }
-
-// #[test]
-// fn inheritance() {
-//     let params = &RunParams { should_fail: false };
-//     compare_interpreter_runs("data/10/input.rl", "data/10/expected.txt", params);
-// }
-
-// #[test]
-// fn methods() {
-//     let params = &RunParams { should_fail: false };
-//     compare_interpreter_runs("data/11/input.rl", "data/11/expected.txt", params);
-// }
-
-// #[test]
-// fn init() {
-//     let params = &RunParams { should_fail: false };
-//     compare_interpreter_runs("data/12/input.rl", "data/12/expected.txt", params);
-// }
-
-// #[test]
-// fn getter() {
-//     let params = &RunParams { should_fail: false };
-//     compare_interpreter_runs("data/13/input.rl", "data/13/expected.txt", params);
-// }
-
-// #[test]
-// fn setter() {
-//     let params = &RunParams { should_fail: false };
-//     compare_interpreter_runs("data/14/input.rl", "data/14/expected.txt", params);
-// }
-
-// #[test]
-// fn this() {
-//     let params = &RunParams { should_fail: false };
-//     compare_interpreter_runs("data/15/input.rl", "data/15/expected.txt", params);
-// }
-
-// #[test]
-// fn super_() {
-//     let params = &RunParams { should_fail: false };
-//     compare_interpreter_runs("data/16/input.rl", "data/16/expected.txt", params);
-// }
+#[test]
+fn nil_value() {
+    let params = &mut RunParams { should_fail: false };
+    compare_interpreter_runs("data/18/input.rl", "data/18/expected.txt", params);
+
+    params.should_fail = true;
+    compare_interpreter_runs("data/18/input_err.rl", "data/18/expected_err.txt", params);
+}
+
+#[test]
+fn syntax_errors() {
+    let params = &RunParams { should_fail: true };
+    compare_interpreter_runs("data/17/input_err.rl", "data/17/expected_err.txt", params);
+    compare_interpreter_runs(
+        "data/17/input_scan_err.rl",
+        "data/17/expected_scan_err.txt",
+        params,
+    );
+}
+
+#[test]
+fn resolver() {
+    let params = &mut RunParams { should_fail: false };
+    compare_interpreter_runs("data/20/input.rl", "data/20/expected.txt", params);
+
+    params.should_fail = true;
+    compare_interpreter_runs("data/20/input_err.rl", "data/20/expected_err.txt", params);
+}
+
+#[test]
+fn natives() {
+    let params = &mut RunParams { should_fail: false };
+    compare_interpreter_runs("data/21/input.rl", "data/21/expected.txt", params);
+
+    params.should_fail = true;
+    compare_interpreter_runs("data/21/input_err.rl", "data/21/expected_err.txt", params);
+    compare_interpreter_runs(
+        "data/21/input_arity_err.rl",
+        "data/21/expected_arity_err.txt",
+        params,
+    );
+}
+
+#[test]
+fn host_natives() {
+    for backend in BACKENDS {
+        let mut natives = NativeRegistry::default();
+        natives.register(
+            "double",
+            LoxCallable::new_native(1, |args| match &args[0] {
+                IResult::Number(n) => Ok(IResult::Number(n * 2.0)),
+                _ => Ok(IResult::Nil),
+            }),
+        );
+        let cursor = Shared::new(Cursor::new(Vec::new()));
+        let mut lox = Lox::with_backend(cursor.clone(), natives, backend);
+
+        lox.eval("print double(21); print len(\"abc\");").unwrap();
+        assert_eq!(
+            String::from_utf8(cursor.borrow().get_ref().clone()).unwrap(),
+            "42\n3\n"
+        );
+    }
+}
+
+#[test]
+fn host_conversions() {
+    for backend in BACKENDS {
+        let mut natives = NativeRegistry::default();
+        natives
+            .register_fn("add", |a: f64, b: f64| a + b)
+            .register_fn("greet", |name: String, excited: bool| {
+                format!("hello {}{}", name, if excited { "!" } else { "" })
+            })
+            .register_fn("twice", |x: Option<f64>| x.map(|x| x * 2.0))
+            .register_fn("range", |n: f64| {
+                (0..n as usize).map(|i| i as f64).collect::<Vec<f64>>()
+            })
+            .register_fn("sum", |xs: Vec<f64>| xs.iter().sum::<f64>())
+            .register_fn("nothing", || ())
+            .register_fn("checked", |x: f64| {
+                if x < 0.0 {
+                    Err(RuntimeError::native(
+                        RuntimeErrorKind::InvalidOperand,
+                        "expected a positive number",
+                    ))
+                } else {
+                    Ok(x.sqrt())
+                }
+            });
+        let cursor = Shared::new(Cursor::new(Vec::new()));
+        let mut lox = Lox::with_backend(cursor.clone(), natives, backend);
+
+        let source = "
+    print add(1, 2);
+    print greet(\"lox\", true);
+    print twice(nil);
+    print twice(4);
+    print range(3);
+    print len(range(5));
+    print sum(range(4));
+    print nothing();
+    print checked(9);
+    ";
+        lox.eval(source).unwrap();
+        assert_eq!(
+            String::from_utf8(cursor.borrow().get_ref().clone()).unwrap(),
+            "3\nhello lox!\nnil\n8\n[0, 1, 2]\n5\n6\nnil\n3\n"
+        );
+
+        // argument type errors and errors returned by the function point at the call
+        for (source, message) in [
+            (
+                "add(\"1\", 2);",
+                "argument 1: expected number but got string",
+            ),
+            (
+                "greet(\"lox\", nil);",
+                "argument 2: expected bool but got nil",
+            ),
+            ("sum(range);", "argument 1: expected list but got function"),
+            ("checked(-1);", "expected a positive number"),
+        ] {
+            let diagnostics = lox.eval(source).unwrap_err().diagnostics();
+            assert_eq!(diagnostics[0].message, message);
+            assert_eq!(diagnostics[0].span.column, 1);
+        }
+    }
+}
+
+#[test]
+fn embedding() {
+    for backend in BACKENDS {
+        let cursor = Shared::new(Cursor::new(Vec::new()));
+        let mut lox = Lox::with_backend(cursor.clone(), NativeRegistry::default(), backend);
+
+        lox.set_global("greeting", "hello");
+        lox.set_global("count", 2.0);
+        let value = lox
+            .eval("var shout = greeting + \"!\"; count = count + 1; count * 2;")
+            .unwrap();
+        assert_eq!(value, IResult::Number(6.0));
+        assert_eq!(
+            lox.get_global("shout").map(|v| v.to_string()),
+            Some("hello!".to_string())
+        );
+        assert_eq!(lox.get_global("count"), Some(IResult::Number(3.0)));
+        assert_eq!(lox.get_global("missing"), None);
+
+        // globals persist between evaluations and functions can be called from the host
+        lox.eval("fun add(a, b) { print a + b; return a + b; }")
+            .unwrap();
+        let sum = lox
+            .call_function("add", vec![IResult::Number(1.0), IResult::Number(2.0)])
+            .unwrap();
+        assert_eq!(sum, IResult::Number(3.0));
+        assert_eq!(
+            String::from_utf8(cursor.borrow().get_ref().clone()).unwrap(),
+            "3\n"
+        );
+
+        let err = lox.call_function("add", vec![]).unwrap_err();
+        assert_eq!(err.to_string(), "expected 2 arguments but got 0");
+        assert!(matches!(
+            lox.call_function("nope", vec![]),
+            Err(LoxError::Runtime(_))
+        ));
+        assert!(matches!(lox.eval("print ;"), Err(LoxError::Syntax(_))));
+
+        // exit stops the script but leaves ending the process to the host
+        assert!(matches!(
+            lox.eval("fun quit() { exit(7); } quit(); print \"not printed\";"),
+            Err(LoxError::Exit(7))
+        ));
+        assert_eq!(lox.eval("1 + 1;").unwrap(), IResult::Number(2.0));
+
+        // running a file shares the same globals
+        assert!(lox.run_file("data/1/input.rl").is_ok());
+        assert!(matches!(
+            lox.run_file("data/missing.rl"),
+            Err(LoxError::Io(_))
+        ));
+    }
+}
+
+#[test]
+fn stack_traces() {
+    let params = &RunParams { should_fail: true };
+    compare_interpreter_runs("data/22/input_err.rl", "data/22/expected_err.txt", params);
+
+    for backend in BACKENDS {
+        let cursor = Shared::new(Cursor::new(Vec::new()));
+        let mut lox = Lox::with_backend(cursor.clone(), NativeRegistry::default(), backend);
+        let source = "fun inner() {\n  return -\"a\";\n}\nfun outer() { inner(); }\nouter();";
+        let Err(LoxError::Runtime(err)) = lox.eval(source) else {
+            panic!("expected a runtime error");
+        };
+        let frames: Vec<(&str, u32)> = err
+            .backtrace
+            .iter()
+            .map(|frame| (frame.function.as_str(), frame.line))
+            .collect();
+        assert_eq!(frames, vec![("inner", 2), ("outer", 4), ("<script>", 5)]);
+
+        // errors outside of any function don't come with a backtrace
+        let err = lox.eval("print -\"a\";").unwrap_err();
+        assert!(err.diagnostics()[0].backtrace.is_empty());
+    }
+}
+
+#[test]
+fn resource_limits() {
+    for backend in BACKENDS {
+        let cursor = Shared::new(Cursor::new(Vec::new()));
+        let mut lox = Lox::with_backend(cursor.clone(), NativeRegistry::default(), backend);
+        let limit_error = |result: Result<IResult, LoxError>| match result {
+            Err(LoxError::Runtime(err)) => {
+                assert_eq!(err.kind, RuntimeErrorKind::ResourceLimitExceeded);
+                err
+            }
+            other => panic!("expected {:?} backend to hit a limit: {:?}", backend, other),
+        };
+
+        lox.set_limits(Limits {
+            max_steps: Some(1000),
+            ..Default::default()
+        });
+        let err = limit_error(lox.eval("var n = 0;\nwhile true { n = n + 1; }"));
+        assert_eq!(err.message, "step limit of 1000 exceeded");
+        assert_eq!(err.line(), 2);
+        assert_eq!(lox.get_global("n"), Some(IResult::Number(1001.0)));
+        // every run gets the whole budget
+        for _ in 0..2 {
+            lox.eval("for (var i = 0; i < 900; i = i + 1) {}").unwrap();
+        }
+
+        lox.set_limits(Limits {
+            max_call_depth: Some(50),
+            ..Default::default()
+        });
+        let err = limit_error(lox.eval("fun down(n) { return down(n + 1); }\ndown(0);"));
+        assert_eq!(err.message, "call depth limit of 50 exceeded");
+        assert_eq!(err.backtrace.len(), 50);
+        limit_error(lox.call_function("down", vec![IResult::Number(0.0)]));
+
+        lox.set_limits(Limits {
+            max_memory: Some(1_000_000),
+            ..Default::default()
+        });
+        // strings count with their length, so doubling one stops well short of the memory
+        // it would take
+        let err = limit_error(lox.eval("var s = \"a\";\nwhile true { s = s + s; }"));
+        assert_eq!(err.message, "memory limit of 1000000 bytes exceeded");
+        assert_eq!(err.line(), 2);
+        assert_eq!(lox.get_global("s").unwrap().to_string().len(), 1 << 18);
+        limit_error(lox.eval("class A {}\nwhile true { A(); }"));
+
+        // the interpreter is left ready for the next run
+        lox.set_limits(Limits::default());
+        lox.eval("print down;").unwrap();
+        assert_eq!(
+            String::from_utf8(cursor.borrow().get_ref().clone()).unwrap(),
+            "LoxCallable { arity: 1, call: fn }\n"
+        );
+    }
+}
+
+#[test]
+fn interrupts() {
+    for backend in BACKENDS {
+        // `stop` interrupts the run calling it
+        let handle: Rc<OnceCell<InterruptHandle>> = Rc::new(OnceCell::new());
+        let mut natives = NativeRegistry::default();
+        let stop_handle = handle.clone();
+        natives.register_fn("stop", move || {
+            if let Some(handle) = stop_handle.get() {
+                handle.interrupt();
+            }
+        });
+        let cursor = Shared::new(Cursor::new(Vec::new()));
+        let mut lox = Lox::with_backend(cursor.clone(), natives, backend);
+        handle.set(lox.interrupt_handle()).unwrap();
+        let interrupted = |result: Result<IResult, LoxError>| match result {
+            Err(LoxError::Runtime(err)) => {
+                assert_eq!(err.kind, RuntimeErrorKind::Interrupted);
+                err
+            }
+            other => panic!(
+                "expected {:?} backend to be interrupted: {:?}",
+                backend, other
+            ),
+        };
+
+        // stopped from another thread while looping forever; interrupts before the
+        // run starts are dropped, so they are repeated until it stops
+        let handle = lox.interrupt_handle();
+        let stopped = Arc::new(AtomicBool::new(false));
+        let done = stopped.clone();
+        let stopper = thread::spawn(move || {
+            while !done.load(Ordering::Relaxed) {
+                thread::sleep(Duration::from_millis(10));
+                handle.interrupt();
+            }
+        });
+        let err = interrupted(lox.eval("var n = 0;\nwhile true { n = n + 1; }"));
+        stopped.store(true, Ordering::Relaxed);
+        stopper.join().unwrap();
+        assert_eq!(err.message, "script interrupted");
+        assert_eq!(err.line(), 2);
+
+        // calls are checked too
+        lox.eval("fun answer() { return 42; }").unwrap();
+        let err = interrupted(lox.eval("print 1;\nstop();\nprint answer();"));
+        assert_eq!(err.line(), 3);
+
+        // the interrupt is used up, and the interpreter runs as before
+        lox.eval("print n > 0;").unwrap();
+
+        // interrupts requested while nothing runs don't stop the next run
+        lox.interrupt_handle().interrupt();
+        lox.eval("print answer();").unwrap();
+        assert_eq!(
+            lox.call_function("answer", vec![]).unwrap(),
+            IResult::Number(42.0)
+        );
+        assert_eq!(
+            String::from_utf8(cursor.borrow().get_ref().clone()).unwrap(),
+            "1\ntrue\n42\n"
+        );
+    }
+}
+
+#[test]
+fn diagnostic_rendering() {
+    let source = "fun f() {\n  var a = 1;\n  var a = 2;\n}\nf();";
+    let mut lox = Lox::with_output(Shared::new(Cursor::new(Vec::new())));
+    let diagnostics = lox.eval(source).unwrap_err().diagnostics();
+    assert_eq!(
+        diagnostics[0].render(source, "notes.rl", ColorMode::Plain),
+        "error: already a variable with this name in this scope\n \
+         --> notes.rl:3:7\n  \
+         |\n\
+         3 |   var a = 2;\n  \
+         |       ^\n  \
+         = note: 'a' is first declared on line 2\n"
+    );
+    assert_eq!(
+        diagnostics[0].render(source, "notes.rl", ColorMode::Color),
+        "\x1b[1;31merror\x1b[0m\x1b[1m: already a variable with this name in this scope\x1b[0m\n \
+         \x1b[1;34m-->\x1b[0m notes.rl:3:7\n  \
+         \x1b[1;34m|\x1b[0m\n\
+         \x1b[1;34m3\x1b[0m \x1b[1;34m|\x1b[0m   var a = 2;\n  \
+         \x1b[1;34m|\x1b[0m       \x1b[1;31m^\x1b[0m\n  \
+         \x1b[1;34m=\x1b[0m note: 'a' is first declared on line 2\n"
+    );
+
+    // runtime errors in calls come with their backtrace
+    let source = "fun f() {\n  return -nil;\n}\nf();";
+    let diagnostics = lox.eval(source).unwrap_err().diagnostics();
+    assert_eq!(
+        diagnostics[0].render(source, "trace.rl", ColorMode::Color),
+        "\x1b[1;31merror\x1b[0m\x1b[1m: invalid operand for plus/minus operator\x1b[0m\n \
+         \x1b[1;34m-->\x1b[0m trace.rl:2:10\n  \
+         \x1b[1;34m|\x1b[0m\n\
+         \x1b[1;34m2\x1b[0m \x1b[1;34m|\x1b[0m   return -nil;\n  \
+         \x1b[1;34m|\x1b[0m          \x1b[1;31m^\x1b[0m\n  \
+         at f (trace.rl:2)\n  \
+         at <script> (trace.rl:4)\n"
+    );
+}
+
+#[test]
+fn source_spans() {
+    let source = "var ab = 12.5;\nprint \"hi\" + ab;";
+    let scanner = Scanner::build_scanner(Cursor::new(source.as_bytes()));
+    let tokens = scanner
+        .map(|t| Rc::new(t.unwrap()))
+        .collect::<Vec<TokenRef>>();
+
+    for token in tokens.iter().filter(|t| t.ttype != TokenType::Eof) {
+        assert_eq!(&source[token.span.start..token.span.end], token.lexeme);
+    }
+    let print = &tokens[5];
+    assert_eq!(print.ttype, TokenType::Print);
+    assert_eq!((print.span.line, print.span.column), (2, 1));
+    let ab = &tokens[8];
+    assert_eq!((ab.span.line, ab.span.column), (2, 14));
+
+    let decls = Parser::new(tokens).parse().unwrap();
+    let print_stmt = StmtDecl::from(decls[1].clone());
+    let span = print_stmt.span();
+    assert_eq!(&source[span.start..span.end], "print \"hi\" + ab;");
+    let print_stmt = print_stmt
+        .stmt
+        .as_any()
+        .downcast_ref::<PrintStmt>()
+        .unwrap();
+    let span = print_stmt.value.span();
+    assert_eq!(&source[span.start..span.end], "\"hi\" + ab");
+}
+
+#[test]
+fn unresolved_jumps() {
+    // hosts can run declarations the resolver never saw
+    let parse = |source: &str| {
+        let scanner = Scanner::build_scanner(Cursor::new(source.as_bytes()));
+        let tokens = scanner.map(|t| Rc::new(t.unwrap())).collect();
+        Parser::new(tokens).parse().unwrap()
+    };
+    let mut interpreter = Interpreter::new_with_out(Shared::new(Cursor::new(Vec::new())));
+    for (source, message) in [
+        ("return;", "can't return from top-level code"),
+        ("{ break; }", "break outside loop"),
+        ("fun f() { continue; }\nf();", "continue outside loop"),
+    ] {
+        let err = interpreter.interpret(parse(source)).unwrap_err();
+        assert_eq!(err.kind, RuntimeErrorKind::InvalidControlFlow);
+        assert_eq!(err.message, message);
+    }
+    // the interpreter is back at the top level
+    interpreter.interpret(parse("var a = 1;")).unwrap();
+    assert_eq!(interpreter.get_global("a"), Some(IResult::Number(1.0)));
+}
+
+#[test]
+fn recursion() {
+    let params = &RunParams { should_fail: false };
+    compare_interpreter_runs(
+        "data/8/input_recursion.rl",
+        "data/8/expected_recursion.txt",
+        params,
+    );
+}
+
+#[test]
+fn stack_overflow() {
+    // both backends stop runaway recursion at the same depth, rather than crashing
+    let params = &RunParams { should_fail: true };
+    compare_interpreter_runs("data/27/input_err.rl", "data/27/expected_err.txt", params);
+
+    // the tree-walker also recurses on expressions, and stops before the native stack
+    // runs out when calls are nested in deep ones
+    let nested = format!(
+        "fun deeper(n) {{ return {}deeper(n + 1){}; }}\ndeeper(0);",
+        "(".repeat(40),
+        ")".repeat(40)
+    );
+    for backend in BACKENDS {
+        let mut lox = Lox::with_backend(
+            Shared::new(Cursor::new(Vec::new())),
+            NativeRegistry::default(),
+            backend,
+        );
+        let Err(LoxError::Runtime(err)) = lox.eval(&nested) else {
+            panic!("expected a runtime error from {:?} backend", backend);
+        };
+        assert_eq!(err.kind, RuntimeErrorKind::StackOverflow);
+    }
+}
+
+#[test]
+fn control_flow() {
+    let params = &RunParams { should_fail: false };
+    compare_interpreter_runs("data/23/input.rl", "data/23/expected.txt", params);
+}
+
+#[test]
+fn lambdas() {
+    let params = &mut RunParams { should_fail: false };
+    compare_interpreter_runs("data/24/input.rl", "data/24/expected.txt", params);
+
+    params.should_fail = true;
+    compare_interpreter_runs("data/24/input_err.rl", "data/24/expected_err.txt", params);
+}
+
+#[test]
+fn garbage_collection() {
+    let params = &RunParams { should_fail: false };
+    for backend in BACKENDS {
+        let before = gc::stats();
+        // a hundred thousand closures that reference themselves, each making a cycle
+        compare_run("data/26/input.rl", "data/26/expected.txt", params, backend);
+        let after = gc::stats();
+        assert!(
+            after.collections > before.collections,
+            "{:?} backend",
+            backend
+        );
+        assert!(
+            after.freed - before.freed >= 100_000,
+            "{:?} backend",
+            backend
+        );
+        assert!(after.live < 50_000, "{:?} backend", backend);
+    }
+
+    // nothing survives the interpreters
+    gc::collect();
+    assert_eq!(gc::stats().live, 0);
+}
+
+#[test]
+fn disassembler() {
+    for (input, expected) in [
+        ("data/25/input.rl", "data/25/expected.txt"),
+        ("data/25/input_closures.rl", "data/25/expected_closures.txt"),
+        ("data/25/input_classes.rl", "data/25/expected_classes.txt"),
+    ] {
+        let source = fs::read_to_string(input).unwrap();
+        let listing = rlox::disassemble(&source).unwrap();
+        assert_eq!(listing, fs::read_to_string(expected).unwrap(), "{}", input);
+    }
+
+    assert!(matches!(
+        rlox::disassemble("print ;"),
+        Err(LoxError::Syntax(_))
+    ));
+}
+
+#[test]
+fn compiled_scripts() {
+    for (input, expected) in [
+        ("data/9/input.rl", "data/9/expected.txt"),
+        ("data/24/input.rl", "data/24/expected.txt"),
+        (
+            "data/25/input_closures.rl",
+            "data/25/expected_closures_run.txt",
+        ),
+    ] {
+        let source = fs::read_to_string(input).unwrap();
+        let bytes = rlox::compile_bytecode(&source).unwrap();
+        assert!(rlox::is_bytecode(&bytes));
+
+        let cursor = Shared::new(Cursor::new(Vec::new()));
+        let mut lox = Lox::with_backend(cursor.clone(), NativeRegistry::default(), Backend::Vm);
+        lox.run_bytecode(&bytes).unwrap();
+        assert_eq!(
+            String::from_utf8(cursor.borrow().get_ref().clone()).unwrap(),
+            fs::read_to_string(expected).unwrap(),
+            "{}",
+            input
+        );
+    }
+
+    // runtime errors still point at the source they were compiled from
+    let source = fs::read_to_string("data/22/input_err.rl").unwrap();
+    let bytes = rlox::compile_bytecode(&source).unwrap();
+    let mut lox = Lox::with_backend(
+        Shared::new(Cursor::new(Vec::new())),
+        NativeRegistry::default(),
+        Backend::Vm,
+    );
+    let from_source = lox.eval(&source).unwrap_err().diagnostics();
+    let from_bytecode = lox.run_bytecode(&bytes).unwrap_err().diagnostics();
+    assert_eq!(from_source[0].message, from_bytecode[0].message);
+    assert_eq!(from_source[0].span, from_bytecode[0].span);
+    assert_eq!(from_source[0].backtrace, from_bytecode[0].backtrace);
+
+    // truncated and corrupted files are rejected without running anything
+    for len in 0..bytes.len() {
+        assert!(matches!(
+            lox.run_bytecode(&bytes[..len]),
+            Err(LoxError::Load(_))
+        ));
+    }
+    for i in 0..bytes.len() {
+        let mut corrupted = bytes.clone();
+        corrupted[i] ^= 0x5a;
+        assert!(
+            matches!(lox.run_bytecode(&corrupted), Err(LoxError::Load(_))),
+            "byte {} flipped",
+            i
+        );
+    }
+    let mut newer = bytes.clone();
+    newer[5] += 1;
+    assert!(matches!(
+        lox.run_bytecode(&newer),
+        Err(LoxError::Load(LoadError::UnsupportedVersion(2)))
+    ));
+
+    // code with a valid checksum is still checked before it runs
+    use OpCode::*;
+    let unbalanced: [&[OpCode]; 5] = [
+        &[GetLocal, Return],
+        &[Pop, Pop, Return],
+        &[Inherit, Return],
+        // the stack is one value higher after the jump is taken
+        &[True, JumpIfFalse, Nil, Return],
+        // the closure is popped while it is captured
+        &[Closure, Pop, Nil, Return],
+    ];
+    for code in unbalanced {
+        let bytes = compiled_code(code);
+        assert!(
+            matches!(
+                lox.run_bytecode(&bytes),
+                Err(LoxError::Load(LoadError::Invalid(_)))
+            ),
+            "{:?}",
+            code
+        );
+    }
+    // values of the wrong type fail the script instead
+    let Err(LoxError::Runtime(err)) =
+        lox.run_bytecode(&compiled_code(&[Nil, Nil, Inherit, Return]))
+    else {
+        panic!("expected a runtime error");
+    };
+    assert_eq!(err.kind, RuntimeErrorKind::InvalidBytecode);
+    assert!(lox
+        .run_bytecode(&compiled_code(&[Closure, CloseUpvalue, Nil, Return]))
+        .is_ok());
+
+    // only the vm runs bytecode
+    let mut lox = Lox::with_output(Shared::new(Cursor::new(Vec::new())));
+    assert!(matches!(lox.run_bytecode(&bytes), Err(LoxError::Io(_))));
+}
+
+// a compiled script running the instructions; GetLocal reads slot 200, jumps skip
+// one instruction and closures capture the slot they go to
+fn compiled_code(ops: &[OpCode]) -> Vec<u8> {
+    let mut chunk = Chunk::default();
+    for op in ops {
+        chunk.write_op(*op, Span::default());
+        match op {
+            OpCode::GetLocal => chunk.write_byte(200),
+            OpCode::JumpIfFalse => chunk.write_u16(1),
+            OpCode::Closure => {
+                let mut body = Chunk::default();
+                body.write_op(OpCode::Nil, Span::default());
+                body.write_op(OpCode::Return, Span::default());
+                let function = Value::Function(Rc::new(Function {
+                    name: "f".into(),
+                    arity: 0,
+                    upvalue_count: 1,
+                    chunk: body,
+                }));
+                let index = chunk.add_constant(function).unwrap();
+                chunk.write_u16(index);
+                chunk.write_byte(1);
+                chunk.write_byte(1);
+            }
+            _ => {}
+        }
+    }
+    serialize::serialize(&Function {
+        name: "<script>".into(),
+        arity: 0,
+        upvalue_count: 0,
+        chunk,
+    })
+}
+
+#[test]
+fn class() {
+    let params = &mut RunParams { should_fail: false };
+    compare_interpreter_runs("data/9/input.rl", "data/9/expected.txt", params);
+
+    params.should_fail = true;
+    compare_interpreter_runs("data/9/input_err.rl", "data/9/expected_err.txt", params);
+}
+
+#[test]
+fn inheritance() {
+    let params = &mut RunParams { should_fail: false };
+    compare_interpreter_runs("data/10/input.rl", "data/10/expected.txt", params);
+
+    params.should_fail = true;
+    compare_interpreter_runs("data/10/input_err.rl", "data/10/expected_err.txt", params);
+    compare_interpreter_runs(
+        "data/10/input_not_class.rl",
+        "data/10/expected_not_class.txt",
+        params,
+    );
+}
+
+#[test]
+fn methods() {
+    let params = &RunParams { should_fail: false };
+    compare_interpreter_runs("data/11/input.rl", "data/11/expected.txt", params);
+}
+
+#[test]
+fn init() {
+    let params = &mut RunParams { should_fail: false };
+    compare_interpreter_runs("data/12/input.rl", "data/12/expected.txt", params);
+
+    params.should_fail = true;
+    compare_interpreter_runs("data/12/input_err.rl", "data/12/expected_err.txt", params);
+}
+
+#[test]
+fn getter() {
+    let params = &mut RunParams { should_fail: false };
+    compare_interpreter_runs("data/13/input.rl", "data/13/expected.txt", params);
+
+    params.should_fail = true;
+    compare_interpreter_runs("data/13/input_err.rl", "data/13/expected_err.txt", params);
+}
+
+#[test]
+fn setter() {
+    let params = &mut RunParams { should_fail: false };
+    compare_interpreter_runs("data/14/input.rl", "data/14/expected.txt", params);
+
+    params.should_fail = true;
+    compare_interpreter_runs("data/14/input_err.rl", "data/14/expected_err.txt", params);
+}
+
+#[test]
+fn this() {
+    let params = &mut RunParams { should_fail: false };
+    compare_interpreter_runs("data/15/input.rl", "data/15/expected.txt", params);
+
+    params.should_fail = true;
+    compare_interpreter_runs("data/15/input_err.rl", "data/15/expected_err.txt", params);
+}
+
+#[test]
+fn super_() {
+    let params = &mut RunParams { should_fail: false };
+    compare_interpreter_runs("data/16/input.rl", "data/16/expected.txt", params);
+
+    params.should_fail = true;
+    compare_interpreter_runs("data/16/input_err.rl", "data/16/expected_err.txt", params);
+}
 
 // tests for if statements
 // tests for out of scope errors
@@ -135,25 +877,42 @@ fn functions() {
 // while loop (with breaks)
 // functions and calls
 
-#[allow(dead_code)]
 fn compare_interpreter_runs(input_program: &str, expected_out_file: &str, params: &RunParams) {
+    for backend in BACKENDS {
+        compare_run(input_program, expected_out_file, params, backend);
+    }
+}
+
+fn compare_run(input_program: &str, expected_out_file: &str, params: &RunParams, backend: Backend) {
     let expected_out = fs::read_to_string(expected_out_file).unwrap();
-    let scanner = Scanner::build_scanner(BufReader::new(File::open(input_program).unwrap()));
+    let source = fs::read_to_string(input_program).unwrap();
     let cursor = Shared::new(Cursor::new(Vec::new()));
 
-    let mut interpreter = Interpreter::new_with_out(cursor.clone());
-    let result = crate::execute(&mut interpreter, scanner);
+    let mut lox = Lox::with_backend(cursor.clone(), NativeRegistry::default(), backend);
+    let result = lox.eval(&source).map_err(|err| err.diagnostics());
 
     if params.should_fail {
-        if let Err(msg) = result {
-            assert_eq!(msg, expected_out);
-        } else {
-            assert!(false, "expected to get error from interpreter");
+        match result {
+            Err(diagnostics) => {
+                let msg = diagnostics
+                    .iter()
+                    .map(|d| d.render(&source, input_program, ColorMode::Plain))
+                    .collect::<Vec<String>>()
+                    .join("\n");
+                assert_eq!(msg, expected_out, "{:?} backend", backend)
+            }
+            Ok(_) => panic!("expected to get error from {:?} backend", backend),
         }
 
         return;
     }
 
+    assert!(
+        result.is_ok(),
+        "unexpected error from {:?} backend",
+        backend
+    );
+
     cursor
         .borrow_mut()
         .seek(std::io::SeekFrom::Start(0))
@@ -165,5 +924,5 @@ fn compare_interpreter_runs(input_program: &str, expected_out_file: &str, params
         .read_to_string(&mut out)
         .expect("read didn't go as expected");
 
-    assert_eq!(out, expected_out);
+    assert_eq!(out, expected_out, "{:?} backend", backend);
 }
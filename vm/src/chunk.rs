@@ -0,0 +1,150 @@
+use scanner::tokens::Span;
+
+use crate::value::Value;
+
+macro_rules! opcodes {
+    ($($(#[$doc:meta])* $name:ident,)*) => {
+        #[repr(u8)]
+        #[derive(Clone, Copy, Debug, PartialEq)]
+        pub enum OpCode {
+            $($(#[$doc])* $name,)*
+        }
+
+        impl OpCode {
+            const ALL: &'static [OpCode] = &[$(OpCode::$name,)*];
+
+            pub fn from_byte(byte: u8) -> Option<OpCode> {
+                Self::ALL.get(byte as usize).copied()
+            }
+        }
+    };
+}
+
+// Instructions are one byte, followed by their operands. Constant, name and
+// jump operands are two bytes (big endian), slots and argument counts one.
+opcodes! {
+    // constant index
+    Constant,
+    Nil,
+    True,
+    False,
+    // the result of an assignment, which isn't a value
+    None,
+    // the value of a variable declared without an initializer
+    Uninitialized,
+    Pop,
+    // stack slot relative to the frame
+    GetLocal,
+    SetLocal,
+    // name constant
+    GetGlobal,
+    DefineGlobal,
+    SetGlobal,
+    // index in the upvalues of the running closure
+    GetUpvalue,
+    SetUpvalue,
+    // name constant; spans: the name and the object
+    GetProperty,
+    SetProperty,
+    // name constant
+    GetSuper,
+    Equal,
+    NotEqual,
+    Greater,
+    GreaterEqual,
+    Less,
+    LessEqual,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Not,
+    Negate,
+    // unary plus, which only checks that the operand is a number
+    Positive,
+    Print,
+    // forward offset
+    Jump,
+    JumpIfFalse,
+    // backward offset
+    Loop,
+    // argument count; spans: the call, the callee and the closing paren
+    Call,
+    // function constant, then a (is_local, index) byte pair per upvalue
+    Closure,
+    CloseUpvalue,
+    Return,
+    // name constant
+    Class,
+    Inherit,
+    // name constant
+    Method,
+}
+
+// A compiled function body: the bytecode, the constants it refers to and where in
+// the source each instruction comes from.
+#[derive(Default)]
+pub struct Chunk {
+    pub code: Vec<u8>,
+    pub constants: Vec<Value>,
+    // line table: (offset, span) pairs sorted by offset, a new pair starting
+    // wherever the span changes. Instructions raising errors at more than one
+    // place get their other spans in pairs with the same offset.
+    pub(crate) spans: Vec<(usize, Span)>,
+}
+
+impl Chunk {
+    pub fn write_op(&mut self, op: OpCode, span: Span) {
+        if self.spans.last().map(|(_, last)| *last) != Some(span) {
+            self.spans.push((self.code.len(), span));
+        }
+        self.code.push(op as u8);
+    }
+
+    // an instruction pointing at several places; the first span is its own
+    pub fn write_op_spans(&mut self, op: OpCode, spans: &[Span]) {
+        for span in spans {
+            self.spans.push((self.code.len(), *span));
+        }
+        self.code.push(op as u8);
+    }
+
+    pub fn write_byte(&mut self, byte: u8) {
+        self.code.push(byte);
+    }
+
+    pub fn write_u16(&mut self, value: u16) {
+        self.code.extend_from_slice(&value.to_be_bytes());
+    }
+
+    pub fn patch_u16(&mut self, offset: usize, value: u16) {
+        self.code[offset..offset + 2].copy_from_slice(&value.to_be_bytes());
+    }
+
+    #[inline]
+    pub fn read_u16(&self, offset: usize) -> u16 {
+        u16::from_be_bytes([self.code[offset], self.code[offset + 1]])
+    }
+
+    // None once the pool is full
+    pub fn add_constant(&mut self, value: Value) -> Option<u16> {
+        let index = u16::try_from(self.constants.len()).ok()?;
+        self.constants.push(value);
+        Some(index)
+    }
+
+    // span of the instruction at offset
+    pub fn span(&self, offset: usize) -> Span {
+        let index = self.spans.partition_point(|(start, _)| *start < offset);
+        match self.spans.get(index) {
+            Some((start, span)) if *start == offset => *span,
+            _ => self.spans[index - 1].1,
+        }
+    }
+
+    // the nth other span of an instruction written with `write_op_spans`
+    pub fn extra_span(&self, offset: usize, n: usize) -> Span {
+        let index = self.spans.partition_point(|(start, _)| *start < offset);
+        self.spans[index + n].1
+    }
+}
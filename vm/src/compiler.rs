@@ -0,0 +1,713 @@
+use std::{collections::HashMap, rc::Rc};
+
+use parser::{
+    ast::{
+        self, Binary, DeclRef, DeclType, ExprStmt, FunDecl, Grouping, Literal, StmtDecl, StmtType,
+        Unary,
+    },
+    error::ParseError,
+    utils::Visitor,
+};
+use scanner::tokens::{Span, Token, TokenRef, TokenType};
+
+use crate::{
+    chunk::{Chunk, OpCode},
+    value::{Function, Value},
+};
+
+#[derive(Clone, Copy, PartialEq)]
+enum FunctionKind {
+    Script,
+    Function,
+    Method,
+    Initializer,
+}
+
+struct Local {
+    name: String,
+    depth: usize,
+    // closed over by a nested function; closed instead of popped at the end of its scope
+    captured: bool,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+struct UpvalueRef {
+    // slot of the enclosing function if local, else index in its upvalues
+    index: u8,
+    is_local: bool,
+}
+
+struct Loop {
+    // scope depth outside of the loop body; break and continue pop what is above it
+    scope_depth: usize,
+    breaks: Vec<usize>,
+    continues: Vec<usize>,
+}
+
+// a function being compiled
+struct FunctionState {
+    kind: FunctionKind,
+    name: Rc<str>,
+    arity: usize,
+    chunk: Chunk,
+    // locals live on the stack, in the order they are declared; slot 0 holds the
+    // called closure, or `this` in methods
+    locals: Vec<Local>,
+    upvalues: Vec<UpvalueRef>,
+    scope_depth: usize,
+    loops: Vec<Loop>,
+    // constant indices of the names used so far
+    names: HashMap<String, u16>,
+}
+
+impl FunctionState {
+    fn new(kind: FunctionKind, name: &str, arity: usize) -> Self {
+        let receiver = match kind {
+            FunctionKind::Method | FunctionKind::Initializer => "this",
+            _ => "",
+        };
+        Self {
+            kind,
+            name: name.into(),
+            arity,
+            chunk: Chunk::default(),
+            locals: vec![Local {
+                name: receiver.to_string(),
+                depth: 0,
+                captured: false,
+            }],
+            upvalues: Vec::new(),
+            scope_depth: 0,
+            loops: Vec::new(),
+            names: HashMap::new(),
+        }
+    }
+}
+
+// Compiles resolved declarations into bytecode for the vm. Top level code becomes
+// the body of a `<script>` function; the value of its last statement, when that is
+// an expression statement, is what the script returns.
+//
+// Scopes follow those of the tree-walker: a block opens a scope, and so do the
+// parameters of a function, its body being a block of its own. The resolver has
+// already reported the static errors; the compiler only reports the limits of the
+// bytecode, such as the number of constants of a function.
+pub struct Compiler {
+    functions: Vec<FunctionState>,
+    errors: Vec<ParseError>,
+}
+
+impl Default for Compiler {
+    fn default() -> Self {
+        Compiler::new()
+    }
+}
+
+impl Compiler {
+    pub fn new() -> Self {
+        Self {
+            functions: Vec::new(),
+            errors: Vec::new(),
+        }
+    }
+
+    pub fn compile(&mut self, decls: &[DeclRef]) -> Result<Rc<Function>, Vec<ParseError>> {
+        self.functions
+            .push(FunctionState::new(FunctionKind::Script, "<script>", 0));
+
+        let last_expression = decls.last().and_then(|decl| expression_stmt(decl.clone()));
+        let count = decls.len() - usize::from(last_expression.is_some());
+        for decl in decls[..count].iter() {
+            self.visit_declaration(decl.clone());
+        }
+        match last_expression {
+            Some(stmt) => {
+                self.visit_expression(&stmt.value);
+                self.emit(OpCode::Return, stmt.span);
+            }
+            None => {
+                self.emit(OpCode::None, Span::default());
+                self.emit(OpCode::Return, Span::default());
+            }
+        }
+
+        let (function, _) = self.end_function();
+        if self.errors.is_empty() {
+            Ok(Rc::new(function))
+        } else {
+            self.functions.clear();
+            Err(std::mem::take(&mut self.errors))
+        }
+    }
+
+    fn current(&mut self) -> &mut FunctionState {
+        self.functions
+            .last_mut()
+            .expect("a function is being compiled")
+    }
+
+    fn chunk(&mut self) -> &mut Chunk {
+        &mut self.current().chunk
+    }
+
+    fn error(&mut self, token: &TokenRef, message: &str) {
+        self.errors.push(ParseError::new(token.clone(), message));
+    }
+
+    fn emit(&mut self, op: OpCode, span: Span) {
+        self.chunk().write_op(op, span);
+    }
+
+    fn emit_u16(&mut self, op: OpCode, operand: u16, span: Span) {
+        self.chunk().write_op(op, span);
+        self.chunk().write_u16(operand);
+    }
+
+    fn emit_u8(&mut self, op: OpCode, operand: u8, span: Span) {
+        self.chunk().write_op(op, span);
+        self.chunk().write_byte(operand);
+    }
+
+    fn constant(&mut self, value: Value, token: &TokenRef) -> u16 {
+        match self.chunk().add_constant(value) {
+            Some(index) => index,
+            None => {
+                self.error(token, "too many constants in one function");
+                0
+            }
+        }
+    }
+
+    // constant holding the name of a variable, property or method
+    fn name_constant(&mut self, token: &TokenRef) -> u16 {
+        if let Some(index) = self.current().names.get(&token.lexeme) {
+            return *index;
+        }
+        let index = self.constant(Value::String(Rc::new(token.lexeme.clone())), token);
+        self.current().names.insert(token.lexeme.clone(), index);
+        index
+    }
+
+    // a forward jump to be patched; returns the offset of its operand
+    fn emit_jump(&mut self, op: OpCode, span: Span) -> usize {
+        self.emit_u16(op, u16::MAX, span);
+        self.chunk().code.len() - 2
+    }
+
+    // points the jump with its operand at offset to the next instruction
+    fn patch_jump(&mut self, offset: usize, token: &TokenRef) {
+        let distance = self.chunk().code.len() - offset - 2;
+        match u16::try_from(distance) {
+            Ok(distance) => self.chunk().patch_u16(offset, distance),
+            Err(_) => self.error(token, "too much code to jump over"),
+        }
+    }
+
+    fn emit_loop(&mut self, start: usize, token: &TokenRef) {
+        self.emit(OpCode::Loop, token.span);
+        let distance = self.chunk().code.len() - start + 2;
+        match u16::try_from(distance) {
+            Ok(distance) => self.chunk().write_u16(distance),
+            Err(_) => {
+                self.chunk().write_u16(0);
+                self.error(token, "loop body too large");
+            }
+        }
+    }
+
+    fn begin_scope(&mut self) {
+        self.current().scope_depth += 1;
+    }
+
+    fn end_scope(&mut self, span: Span) {
+        let state = self.current();
+        state.scope_depth -= 1;
+        let depth = state.scope_depth;
+        while let Some(local) = self.current().locals.last() {
+            if local.depth <= depth {
+                break;
+            }
+            let op = if local.captured {
+                OpCode::CloseUpvalue
+            } else {
+                OpCode::Pop
+            };
+            self.emit(op, span);
+            self.current().locals.pop();
+        }
+    }
+
+    // pops the locals declared deeper than depth, leaving them declared; used to jump
+    // out of a loop body
+    fn discard_locals(&mut self, depth: usize, span: Span) {
+        let ops: Vec<OpCode> = self
+            .current()
+            .locals
+            .iter()
+            .rev()
+            .take_while(|local| local.depth > depth)
+            .map(|local| {
+                if local.captured {
+                    OpCode::CloseUpvalue
+                } else {
+                    OpCode::Pop
+                }
+            })
+            .collect();
+        for op in ops {
+            self.emit(op, span);
+        }
+    }
+
+    fn add_local(&mut self, name: &TokenRef) {
+        if self.current().locals.len() > u8::MAX as usize {
+            self.error(name, "too many local variables in function");
+            return;
+        }
+        let depth = self.current().scope_depth;
+        self.current().locals.push(Local {
+            name: name.lexeme.clone(),
+            depth,
+            captured: false,
+        });
+    }
+
+    // binds the value on top of the stack to name: a global at the top level of the
+    // script, the next local slot anywhere else
+    fn define_variable(&mut self, name: &TokenRef, span: Span) {
+        let state = self.current();
+        if state.kind == FunctionKind::Script && state.scope_depth == 0 {
+            let index = self.name_constant(name);
+            self.emit_u16(OpCode::DefineGlobal, index, span);
+        } else {
+            self.add_local(name);
+        }
+    }
+
+    fn resolve_local(&self, function: usize, name: &str) -> Option<u8> {
+        self.functions[function]
+            .locals
+            .iter()
+            .rposition(|local| local.name == name)
+            .map(|slot| slot as u8)
+    }
+
+    // looks name up in the functions enclosing `function`, capturing it on the way
+    fn resolve_upvalue(&mut self, function: usize, name: &TokenRef) -> Option<u8> {
+        if function == 0 {
+            return None;
+        }
+        if let Some(slot) = self.resolve_local(function - 1, &name.lexeme) {
+            self.functions[function - 1].locals[slot as usize].captured = true;
+            return Some(self.add_upvalue(function, slot, true, name));
+        }
+        let index = self.resolve_upvalue(function - 1, name)?;
+        Some(self.add_upvalue(function, index, false, name))
+    }
+
+    fn add_upvalue(&mut self, function: usize, index: u8, is_local: bool, name: &TokenRef) -> u8 {
+        let upvalue = UpvalueRef { index, is_local };
+        let upvalues = &mut self.functions[function].upvalues;
+        if let Some(existing) = upvalues.iter().position(|u| *u == upvalue) {
+            return existing as u8;
+        }
+        if upvalues.len() > u8::MAX as usize {
+            self.error(name, "too many closure variables in function");
+            return 0;
+        }
+        upvalues.push(upvalue);
+        (upvalues.len() - 1) as u8
+    }
+
+    fn get_variable(&mut self, name: &TokenRef) {
+        let function = self.functions.len() - 1;
+        if let Some(slot) = self.resolve_local(function, &name.lexeme) {
+            self.emit_u8(OpCode::GetLocal, slot, name.span);
+        } else if let Some(index) = self.resolve_upvalue(function, name) {
+            self.emit_u8(OpCode::GetUpvalue, index, name.span);
+        } else {
+            let index = self.name_constant(name);
+            self.emit_u16(OpCode::GetGlobal, index, name.span);
+        }
+    }
+
+    fn set_variable(&mut self, name: &TokenRef) {
+        let function = self.functions.len() - 1;
+        if let Some(slot) = self.resolve_local(function, &name.lexeme) {
+            self.emit_u8(OpCode::SetLocal, slot, name.span);
+        } else if let Some(index) = self.resolve_upvalue(function, name) {
+            self.emit_u8(OpCode::SetUpvalue, index, name.span);
+        } else {
+            let index = self.name_constant(name);
+            self.emit_u16(OpCode::SetGlobal, index, name.span);
+        }
+    }
+
+    // `this` and `super` are looked up like variables
+    fn get_keyword(&mut self, keyword: &TokenRef, name: &str) {
+        self.get_variable(&synthetic(keyword, name));
+    }
+
+    fn end_function(&mut self) -> (Function, Vec<UpvalueRef>) {
+        let state = self.functions.pop().expect("a function is being compiled");
+        let function = Function {
+            name: state.name,
+            arity: state.arity,
+            upvalue_count: state.upvalues.len(),
+            chunk: state.chunk,
+        };
+        (function, state.upvalues)
+    }
+
+    // compiles the function and leaves a closure over it on the stack
+    fn function(&mut self, decl: &FunDecl, kind: FunctionKind) {
+        self.functions.push(FunctionState::new(
+            kind,
+            &decl.identifier.lexeme,
+            decl.params.len(),
+        ));
+        self.begin_scope();
+        for param in decl.params.iter() {
+            self.add_local(param);
+        }
+        self.visit_block_stmt(&decl.body);
+        self.emit_implicit_return(decl.span);
+
+        let (function, upvalues) = self.end_function();
+        let index = self.constant(Value::Function(Rc::new(function)), &decl.identifier);
+        self.emit_u16(OpCode::Closure, index, decl.span);
+        for upvalue in upvalues {
+            self.chunk().write_byte(u8::from(upvalue.is_local));
+            self.chunk().write_byte(upvalue.index);
+        }
+    }
+
+    // what a function returns when it doesn't reach a return statement with a value
+    fn emit_implicit_return(&mut self, span: Span) {
+        if self.current().kind == FunctionKind::Initializer {
+            self.emit_u8(OpCode::GetLocal, 0, span);
+        } else {
+            self.emit(OpCode::Nil, span);
+        }
+        self.emit(OpCode::Return, span);
+    }
+}
+
+// an identifier token standing for `this` or `super` at the place of token
+fn synthetic(token: &TokenRef, name: &str) -> TokenRef {
+    Rc::new(Token {
+        ttype: TokenType::Identifier,
+        lexeme: name.to_string(),
+        line_num: token.line_num,
+        span: token.span,
+    })
+}
+
+// the statement of decl if it is an expression statement
+fn expression_stmt(decl: DeclRef) -> Option<ExprStmt> {
+    if decl.decl_type() != DeclType::Stmt {
+        return None;
+    }
+    let stmt = decl.as_ref().as_any().downcast_ref::<StmtDecl>()?;
+    if stmt.stmt.stmt_type() != StmtType::Expression {
+        return None;
+    }
+    let expr = stmt.stmt.as_ref().as_any().downcast_ref::<ExprStmt>()?;
+    Some(ExprStmt {
+        value: expr.value.clone(),
+        span: expr.span,
+    })
+}
+
+impl Visitor<()> for Compiler {
+    fn visit_var_decl(&mut self, decl: &ast::VarDecl) {
+        match &decl.rhs {
+            Some(rhs) => self.visit_expression(rhs),
+            None => self.emit(OpCode::Uninitialized, decl.span),
+        }
+        self.define_variable(&decl.identifier, decl.span);
+    }
+
+    fn visit_fun_decl(&mut self, decl: Rc<FunDecl>) {
+        let global = {
+            let state = self.current();
+            state.kind == FunctionKind::Script && state.scope_depth == 0
+        };
+        if global {
+            self.function(&decl, FunctionKind::Function);
+            self.define_variable(&decl.identifier, decl.span);
+        } else {
+            // declared first so that the function can call itself
+            self.add_local(&decl.identifier);
+            self.function(&decl, FunctionKind::Function);
+        }
+    }
+
+    fn visit_class_decl(&mut self, decl: &ast::ClassDecl) {
+        let name = self.name_constant(&decl.identifier);
+        self.emit_u16(OpCode::Class, name, decl.identifier.span);
+        self.define_variable(&decl.identifier, decl.span);
+
+        if let Some(superclass) = &decl.superclass {
+            self.visit_expression(superclass);
+            // methods see the superclass as `super`, in a scope around them
+            self.begin_scope();
+            self.add_local(&synthetic(&decl.identifier, "super"));
+            self.get_variable(&decl.identifier);
+            self.emit(OpCode::Inherit, superclass.span());
+        }
+
+        self.get_variable(&decl.identifier);
+        for method in decl.methods.iter() {
+            let kind = if method.identifier.lexeme == "init" {
+                FunctionKind::Initializer
+            } else {
+                FunctionKind::Method
+            };
+            self.function(method, kind);
+            let name = self.name_constant(&method.identifier);
+            self.emit_u16(OpCode::Method, name, method.span);
+        }
+        self.emit(OpCode::Pop, decl.span);
+
+        if decl.superclass.is_some() {
+            self.end_scope(decl.span);
+        }
+    }
+
+    fn visit_print_stmt(&mut self, stmt: &ast::PrintStmt) {
+        self.visit_expression(&stmt.value);
+        self.emit(OpCode::Print, stmt.keyword.span);
+    }
+
+    fn visit_expression_stmt(&mut self, stmt: &ExprStmt) {
+        self.visit_expression(&stmt.value);
+        self.emit(OpCode::Pop, stmt.span);
+    }
+
+    fn visit_block_stmt(&mut self, stmt: &ast::BlockStmt) {
+        self.begin_scope();
+        for decl in stmt.declarations.iter() {
+            self.visit_declaration(decl.clone());
+        }
+        self.end_scope(stmt.span);
+    }
+
+    fn visit_if_stmt(&mut self, stmt: &ast::IfStmt) {
+        self.visit_expression(&stmt.condition);
+        let then_jump = self.emit_jump(OpCode::JumpIfFalse, stmt.keyword.span);
+        self.emit(OpCode::Pop, stmt.keyword.span);
+        self.visit_statement(&stmt.then_b);
+        let else_jump = self.emit_jump(OpCode::Jump, stmt.keyword.span);
+        self.patch_jump(then_jump, &stmt.keyword);
+        self.emit(OpCode::Pop, stmt.keyword.span);
+        if let Some(else_b) = &stmt.else_b {
+            self.visit_statement(else_b);
+        }
+        self.patch_jump(else_jump, &stmt.keyword);
+    }
+
+    fn visit_while_stmt(&mut self, stmt: &ast::WhileStmt) {
+        let start = self.chunk().code.len();
+        self.visit_expression(&stmt.condition);
+        let exit_jump = self.emit_jump(OpCode::JumpIfFalse, stmt.keyword.span);
+        self.emit(OpCode::Pop, stmt.keyword.span);
+
+        let scope_depth = self.current().scope_depth;
+        self.current().loops.push(Loop {
+            scope_depth,
+            breaks: Vec::new(),
+            continues: Vec::new(),
+        });
+        self.visit_block_stmt(&stmt.body);
+        let lp = self
+            .current()
+            .loops
+            .pop()
+            .expect("the loop was pushed above");
+
+        for jump in lp.continues {
+            self.patch_jump(jump, &stmt.keyword);
+        }
+        if let Some(increment) = &stmt.increment {
+            self.visit_expression(increment);
+            self.emit(OpCode::Pop, increment.span());
+        }
+        self.emit_loop(start, &stmt.keyword);
+
+        self.patch_jump(exit_jump, &stmt.keyword);
+        self.emit(OpCode::Pop, stmt.keyword.span);
+        for jump in lp.breaks {
+            self.patch_jump(jump, &stmt.keyword);
+        }
+    }
+
+    fn visit_break_stmt(&mut self, stmt: &ast::BreakStmt) {
+        // the resolver rejects breaks outside of loops
+        let depth = self.current().loops.last().unwrap().scope_depth;
+        self.discard_locals(depth, stmt.span);
+        let jump = self.emit_jump(OpCode::Jump, stmt.span);
+        self.current().loops.last_mut().unwrap().breaks.push(jump);
+    }
+
+    fn visit_continue_stmt(&mut self, stmt: &ast::ContinueStmt) {
+        // the resolver rejects continues outside of loops
+        let depth = self.current().loops.last().unwrap().scope_depth;
+        self.discard_locals(depth, stmt.span);
+        let jump = self.emit_jump(OpCode::Jump, stmt.span);
+        self.current()
+            .loops
+            .last_mut()
+            .unwrap()
+            .continues
+            .push(jump);
+    }
+
+    fn visit_return_stmt(&mut self, stmt: &ast::ReturnStmt) {
+        match &stmt.value {
+            Some(value) => {
+                self.visit_expression(value);
+                self.emit(OpCode::Return, stmt.span);
+            }
+            // the resolver rejects returning a value from an initializer
+            None => self.emit_implicit_return(stmt.span),
+        }
+    }
+
+    fn visit_literal(&mut self, lit: &Literal) {
+        let span = lit.value.span;
+        match &lit.value.ttype {
+            TokenType::String(contents) => {
+                let index = self.constant(Value::String(Rc::new(contents.clone())), &lit.value);
+                self.emit_u16(OpCode::Constant, index, span);
+            }
+            TokenType::Number(value) => {
+                let index = self.constant(Value::Number(*value), &lit.value);
+                self.emit_u16(OpCode::Constant, index, span);
+            }
+            TokenType::True => self.emit(OpCode::True, span),
+            TokenType::False => self.emit(OpCode::False, span),
+            TokenType::Nil => self.emit(OpCode::Nil, span),
+            TokenType::Identifier => self.get_variable(&lit.value),
+            _ => self.error(&lit.value, "invalid token found; expected literal"),
+        }
+    }
+
+    fn visit_grouping(&mut self, grp: &Grouping) {
+        self.visit_expression(&grp.expr);
+    }
+
+    fn visit_unary(&mut self, unr: &Unary) {
+        self.visit_expression(&unr.expr);
+        let op = match unr.operator.ttype {
+            TokenType::Minus => OpCode::Negate,
+            TokenType::Plus => OpCode::Positive,
+            TokenType::Bang => OpCode::Not,
+            _ => {
+                self.error(
+                    &unr.operator,
+                    "invalid token found; expected unary operator",
+                );
+                return;
+            }
+        };
+        self.emit(op, unr.operator.span);
+    }
+
+    fn visit_binary(&mut self, bin: &Binary) {
+        self.visit_expression(&bin.left);
+        self.visit_expression(&bin.right);
+        let op = match bin.operator.ttype {
+            TokenType::Plus => OpCode::Add,
+            TokenType::Minus => OpCode::Subtract,
+            TokenType::Star => OpCode::Multiply,
+            TokenType::Slash => OpCode::Divide,
+            TokenType::Greater => OpCode::Greater,
+            TokenType::GreaterEqual => OpCode::GreaterEqual,
+            TokenType::Less => OpCode::Less,
+            TokenType::LessEqual => OpCode::LessEqual,
+            TokenType::EqualEqual => OpCode::Equal,
+            TokenType::BangEqual => OpCode::NotEqual,
+            _ => {
+                self.error(
+                    &bin.operator,
+                    "invalid token found; expected binary operator",
+                );
+                return;
+            }
+        };
+        self.emit(op, bin.operator.span);
+    }
+
+    fn visit_logical(&mut self, logic: &ast::Logical) {
+        // the operand that decides the outcome is the result, not a bool
+        self.visit_expression(&logic.left);
+        let span = logic.operator.span;
+        if logic.operator.ttype == TokenType::Or {
+            let else_jump = self.emit_jump(OpCode::JumpIfFalse, span);
+            let end_jump = self.emit_jump(OpCode::Jump, span);
+            self.patch_jump(else_jump, &logic.operator);
+            self.emit(OpCode::Pop, span);
+            self.visit_expression(&logic.right);
+            self.patch_jump(end_jump, &logic.operator);
+        } else {
+            let end_jump = self.emit_jump(OpCode::JumpIfFalse, span);
+            self.emit(OpCode::Pop, span);
+            self.visit_expression(&logic.right);
+            self.patch_jump(end_jump, &logic.operator);
+        }
+    }
+
+    fn visit_assign(&mut self, assign: &ast::Assign) {
+        self.visit_expression(&assign.value);
+        self.set_variable(&assign.identifier);
+    }
+
+    fn visit_call(&mut self, call: &ast::Call) {
+        self.visit_expression(&call.callee);
+        for argument in call.arguments.iter() {
+            self.visit_expression(argument);
+        }
+        let Ok(count) = u8::try_from(call.arguments.len()) else {
+            self.error(&call.paren, "can't have more than 255 arguments");
+            return;
+        };
+        self.chunk().write_op_spans(
+            OpCode::Call,
+            &[call.span, call.callee.span(), call.paren.span],
+        );
+        self.chunk().write_byte(count);
+    }
+
+    fn visit_get(&mut self, get: &ast::Get) {
+        self.visit_expression(&get.object);
+        let name = self.name_constant(&get.name);
+        self.chunk()
+            .write_op_spans(OpCode::GetProperty, &[get.name.span, get.object.span()]);
+        self.chunk().write_u16(name);
+    }
+
+    fn visit_set(&mut self, set: &ast::Set) {
+        self.visit_expression(&set.object);
+        self.visit_expression(&set.value);
+        let name = self.name_constant(&set.name);
+        self.chunk()
+            .write_op_spans(OpCode::SetProperty, &[set.name.span, set.object.span()]);
+        self.chunk().write_u16(name);
+    }
+
+    fn visit_this(&mut self, this: &ast::This) {
+        self.get_keyword(&this.keyword, "this");
+    }
+
+    fn visit_super(&mut self, sup: &ast::Super) {
+        self.get_keyword(&sup.keyword, "this");
+        self.get_keyword(&sup.keyword, "super");
+        let name = self.name_constant(&sup.method);
+        self.emit_u16(OpCode::GetSuper, name, sup.span);
+    }
+
+    fn visit_lambda(&mut self, lambda: &ast::Lambda) {
+        self.function(&lambda.function, FunctionKind::Function);
+    }
+}
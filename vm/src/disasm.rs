@@ -0,0 +1,150 @@
+use std::fmt::Write;
+
+use crate::{
+    chunk::{Chunk, OpCode},
+    value::{Function, Value},
+};
+
+// Renders compiled functions as text, one instruction per line:
+//
+//     0003    2 GetGlobal           1 'a'
+//
+// the offset, the source line ('|' when it is the line of the previous
+// instruction), the opcode and its operands. Constants are shown with their value
+// and jumps with their target. Functions found in the constant pool follow the
+// one declaring them.
+pub struct Disassembler {
+    out: String,
+}
+
+impl Disassembler {
+    pub fn disassemble(function: &Function) -> String {
+        let mut disassembler = Disassembler { out: String::new() };
+        disassembler.function(function);
+        disassembler.out
+    }
+
+    fn function(&mut self, function: &Function) {
+        if !self.out.is_empty() {
+            self.out.push('\n');
+        }
+        let _ = writeln!(
+            self.out,
+            "== {} (arity {}, upvalues {}) ==",
+            function.name, function.arity, function.upvalue_count
+        );
+
+        let chunk = &function.chunk;
+        let mut offset = 0;
+        let mut line = None;
+        while offset < chunk.code.len() {
+            offset = self.instruction(chunk, offset, &mut line);
+        }
+
+        for constant in chunk.constants.iter() {
+            if let Value::Function(nested) = constant {
+                self.function(nested);
+            }
+        }
+    }
+
+    // writes the instruction at offset, returns the offset of the next one
+    fn instruction(&mut self, chunk: &Chunk, offset: usize, line: &mut Option<u32>) -> usize {
+        let current = chunk.span(offset).line;
+        let _ = if *line == Some(current) {
+            write!(self.out, "{:04}    | ", offset)
+        } else {
+            write!(self.out, "{:04} {:>4} ", offset, current)
+        };
+        *line = Some(current);
+
+        let Some(op) = OpCode::from_byte(chunk.code[offset]) else {
+            let _ = writeln!(self.out, "<invalid {}>", chunk.code[offset]);
+            return offset + 1;
+        };
+        let name = format!("{:?}", op);
+        match op {
+            OpCode::Constant
+            | OpCode::GetGlobal
+            | OpCode::DefineGlobal
+            | OpCode::SetGlobal
+            | OpCode::GetProperty
+            | OpCode::SetProperty
+            | OpCode::GetSuper
+            | OpCode::Class
+            | OpCode::Method => {
+                let index = chunk.read_u16(offset + 1);
+                let _ = writeln!(
+                    self.out,
+                    "{:<16}{:>4} {}",
+                    name,
+                    index,
+                    constant(chunk, index)
+                );
+                offset + 3
+            }
+            OpCode::GetLocal
+            | OpCode::SetLocal
+            | OpCode::GetUpvalue
+            | OpCode::SetUpvalue
+            | OpCode::Call => {
+                let _ = writeln!(self.out, "{:<16}{:>4}", name, chunk.code[offset + 1]);
+                offset + 2
+            }
+            OpCode::Jump | OpCode::JumpIfFalse => {
+                let target = offset + 3 + chunk.read_u16(offset + 1) as usize;
+                let _ = writeln!(self.out, "{:<16}{:>4} -> {:04}", name, "", target);
+                offset + 3
+            }
+            OpCode::Loop => {
+                let target = offset + 3 - chunk.read_u16(offset + 1) as usize;
+                let _ = writeln!(self.out, "{:<16}{:>4} -> {:04}", name, "", target);
+                offset + 3
+            }
+            OpCode::Closure => {
+                let index = chunk.read_u16(offset + 1);
+                let _ = writeln!(
+                    self.out,
+                    "{:<16}{:>4} {}",
+                    name,
+                    index,
+                    constant(chunk, index)
+                );
+                let upvalues = match &chunk.constants[index as usize] {
+                    Value::Function(function) => function.upvalue_count,
+                    _ => 0,
+                };
+                let mut next = offset + 3;
+                for _ in 0..upvalues {
+                    let kind = if chunk.code[next] == 1 {
+                        "local"
+                    } else {
+                        "upvalue"
+                    };
+                    let _ = writeln!(
+                        self.out,
+                        "{:04}    |   {} {}",
+                        next,
+                        kind,
+                        chunk.code[next + 1]
+                    );
+                    next += 2;
+                }
+                next
+            }
+            _ => {
+                let _ = writeln!(self.out, "{}", name);
+                offset + 1
+            }
+        }
+    }
+}
+
+fn constant(chunk: &Chunk, index: u16) -> String {
+    match chunk.constants.get(index as usize) {
+        Some(Value::String(s)) => format!("'{}'", s),
+        Some(Value::Function(function)) => format!("<fn {}>", function.name),
+        Some(value) => format!("{}", value),
+        None => "<missing>".to_string(),
+    }
+}
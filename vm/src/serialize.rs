@@ -0,0 +1,548 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    fmt::Display,
+    rc::Rc,
+};
+
+use scanner::tokens::Span;
+
+use crate::{
+    chunk::{Chunk, OpCode},
+    value::{Function, Value},
+};
+
+// Compiled scripts saved to disk, to be run without their source.
+//
+// The file starts with a header: the magic bytes, the format version (u16), then
+// the length (u32) and checksum (u32, FNV-1a) of the rest, which is the script
+// function. A function is its name, arity and upvalue count, then its code,
+// constant pool and line table. Constants are tagged numbers, strings or nested
+// functions. Numbers are u32 (counts, lengths, offsets) and f64, big endian like
+// the operands in the code.
+//
+// The checksum catches files corrupted on the way; the checks done while loading
+// keep a file that passes it, such as one written by another version of the
+// compiler, from sending the vm off its code, stack or constant pool.
+pub const MAGIC: &[u8; 4] = b"RLXC";
+pub const VERSION: u16 = 1;
+
+const HEADER_LEN: usize = MAGIC.len() + 2 + 4 + 4;
+// functions declared inside functions; far more than the stack of the compiler allows
+const MAX_NESTING: usize = 256;
+
+const TAG_NUMBER: u8 = 0;
+const TAG_STRING: u8 = 1;
+const TAG_FUNCTION: u8 = 2;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum LoadError {
+    NotBytecode,
+    UnsupportedVersion(u16),
+    Truncated,
+    ChecksumMismatch,
+    Invalid(String),
+}
+
+impl Display for LoadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LoadError::NotBytecode => write!(f, "not a compiled lox script"),
+            LoadError::UnsupportedVersion(version) => write!(
+                f,
+                "compiled for bytecode version {} but this is version {}",
+                version, VERSION
+            ),
+            LoadError::Truncated => write!(f, "compiled script is truncated"),
+            LoadError::ChecksumMismatch => {
+                write!(f, "compiled script is corrupted: checksum mismatch")
+            }
+            LoadError::Invalid(reason) => write!(f, "compiled script is corrupted: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for LoadError {}
+
+pub fn serialize(script: &Function) -> Vec<u8> {
+    let mut payload = Writer { out: Vec::new() };
+    payload.function(script);
+
+    let mut out = Vec::with_capacity(HEADER_LEN + payload.out.len());
+    out.extend_from_slice(MAGIC);
+    out.extend_from_slice(&VERSION.to_be_bytes());
+    out.extend_from_slice(&(payload.out.len() as u32).to_be_bytes());
+    out.extend_from_slice(&checksum(&payload.out).to_be_bytes());
+    out.extend_from_slice(&payload.out);
+    out
+}
+
+pub fn deserialize(bytes: &[u8]) -> Result<Rc<Function>, LoadError> {
+    if !is_bytecode(bytes) {
+        return Err(LoadError::NotBytecode);
+    }
+    let mut header = Reader { bytes, pos: 4 };
+    let version = header.u16().map_err(|_| LoadError::Truncated)?;
+    if version != VERSION {
+        return Err(LoadError::UnsupportedVersion(version));
+    }
+    let len = header.len()?;
+    let expected = header.u32()?;
+    let payload = &bytes[HEADER_LEN..];
+    if payload.len() < len {
+        return Err(LoadError::Truncated);
+    }
+    if payload.len() > len {
+        return Err(invalid("trailing bytes after the script"));
+    }
+    if checksum(payload) != expected {
+        return Err(LoadError::ChecksumMismatch);
+    }
+
+    let mut reader = Reader {
+        bytes: payload,
+        pos: 0,
+    };
+    let script = reader.function(0)?;
+    if reader.pos != payload.len() {
+        return Err(invalid("trailing bytes after the script"));
+    }
+    if script.arity != 0 || script.upvalue_count != 0 {
+        return Err(invalid("the script can't take arguments or upvalues"));
+    }
+    Ok(Rc::new(script))
+}
+
+// whether the bytes start like a compiled script, to tell them apart from source
+pub fn is_bytecode(bytes: &[u8]) -> bool {
+    bytes.starts_with(MAGIC)
+}
+
+fn invalid(reason: &str) -> LoadError {
+    LoadError::Invalid(reason.to_string())
+}
+
+fn checksum(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0x811c9dc5, |hash: u32, byte| {
+        (hash ^ *byte as u32).wrapping_mul(0x01000193)
+    })
+}
+
+struct Writer {
+    out: Vec<u8>,
+}
+
+impl Writer {
+    fn u32(&mut self, value: usize) {
+        let value = u32::try_from(value).expect("compiled scripts fit in u32 sizes");
+        self.out.extend_from_slice(&value.to_be_bytes());
+    }
+
+    fn string(&mut self, value: &str) {
+        self.u32(value.len());
+        self.out.extend_from_slice(value.as_bytes());
+    }
+
+    fn function(&mut self, function: &Function) {
+        self.string(&function.name);
+        self.u32(function.arity);
+        self.u32(function.upvalue_count);
+
+        let chunk = &function.chunk;
+        self.u32(chunk.code.len());
+        self.out.extend_from_slice(&chunk.code);
+
+        self.u32(chunk.constants.len());
+        for constant in chunk.constants.iter() {
+            match constant {
+                Value::Number(n) => {
+                    self.out.push(TAG_NUMBER);
+                    self.out.extend_from_slice(&n.to_be_bytes());
+                }
+                Value::String(s) => {
+                    self.out.push(TAG_STRING);
+                    self.string(s);
+                }
+                Value::Function(nested) => {
+                    self.out.push(TAG_FUNCTION);
+                    self.function(nested);
+                }
+                other => unreachable!("the compiler doesn't make {:?} constants", other),
+            }
+        }
+
+        self.u32(chunk.spans.len());
+        for (offset, span) in chunk.spans.iter() {
+            for value in [*offset, span.start, span.end] {
+                self.u32(value);
+            }
+            self.u32(span.line as usize);
+            self.u32(span.column as usize);
+        }
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl Reader<'_> {
+    fn take(&mut self, len: usize) -> Result<&[u8], LoadError> {
+        let end = self.pos.checked_add(len).ok_or(LoadError::Truncated)?;
+        let bytes = self.bytes.get(self.pos..end).ok_or(LoadError::Truncated)?;
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, LoadError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, LoadError> {
+        let bytes = self.take(2)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32, LoadError> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn len(&mut self) -> Result<usize, LoadError> {
+        Ok(self.u32()? as usize)
+    }
+
+    fn f64(&mut self) -> Result<f64, LoadError> {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(self.take(8)?);
+        Ok(f64::from_be_bytes(bytes))
+    }
+
+    fn string(&mut self) -> Result<String, LoadError> {
+        let len = self.len()?;
+        let bytes = self.take(len)?.to_vec();
+        String::from_utf8(bytes).map_err(|_| invalid("string is not valid utf-8"))
+    }
+
+    fn function(&mut self, depth: usize) -> Result<Function, LoadError> {
+        if depth > MAX_NESTING {
+            return Err(invalid("functions are nested too deeply"));
+        }
+        let name = self.string()?;
+        let arity = self.len()?;
+        let upvalue_count = self.len()?;
+        if arity > u8::MAX as usize || upvalue_count > u8::MAX as usize + 1 {
+            return Err(invalid("too many parameters or upvalues"));
+        }
+
+        let len = self.len()?;
+        let code = self.take(len)?.to_vec();
+
+        let count = self.len()?;
+        let mut constants = Vec::new();
+        for _ in 0..count {
+            let constant = match self.u8()? {
+                TAG_NUMBER => Value::Number(self.f64()?),
+                TAG_STRING => Value::String(Rc::new(self.string()?)),
+                TAG_FUNCTION => Value::Function(Rc::new(self.function(depth + 1)?)),
+                tag => return Err(LoadError::Invalid(format!("unknown constant tag {}", tag))),
+            };
+            constants.push(constant);
+        }
+
+        let count = self.len()?;
+        let mut spans = Vec::new();
+        for _ in 0..count {
+            let offset = self.len()?;
+            let start = self.len()?;
+            let end = self.len()?;
+            let line = self.u32()?;
+            let column = self.u32()?;
+            spans.push((
+                offset,
+                Span {
+                    start,
+                    end,
+                    line,
+                    column,
+                },
+            ));
+        }
+
+        let function = Function {
+            name: name.into(),
+            arity,
+            upvalue_count,
+            chunk: Chunk {
+                code,
+                constants,
+                spans,
+            },
+        };
+        validate(&function)?;
+        Ok(function)
+    }
+}
+
+// checks that running the function stays within its code, stack, constants and
+// upvalues
+fn validate(function: &Function) -> Result<(), LoadError> {
+    let chunk = &function.chunk;
+    let code = &chunk.code;
+    if code.is_empty() {
+        return Err(invalid("function without code"));
+    }
+    if chunk.spans.first().map(|(offset, _)| *offset) != Some(0)
+        || chunk.spans.windows(2).any(|pair| pair[0].0 > pair[1].0)
+    {
+        return Err(invalid("line table out of order"));
+    }
+
+    let operand = |offset: usize| -> Result<usize, LoadError> {
+        match code.get(offset..offset + 2) {
+            Some(bytes) => Ok(u16::from_be_bytes([bytes[0], bytes[1]]) as usize),
+            None => Err(invalid("instruction runs past the end of the code")),
+        }
+    };
+    let byte = |offset: usize| -> Result<usize, LoadError> {
+        code.get(offset)
+            .map(|b| *b as usize)
+            .ok_or_else(|| invalid("instruction runs past the end of the code"))
+    };
+    let name = |index: usize| -> Result<(), LoadError> {
+        match chunk.constants.get(index) {
+            Some(Value::String(_)) => Ok(()),
+            _ => Err(invalid("name operand isn't a string constant")),
+        }
+    };
+    // instructions pointing at more than one place need as many spans
+    let spans = |offset: usize, needed: usize| -> Result<(), LoadError> {
+        let count = chunk.spans.iter().filter(|(at, _)| *at == offset).count();
+        if count < needed {
+            return Err(invalid("line table misses spans of an instruction"));
+        }
+        Ok(())
+    };
+
+    // instruction lengths by offset
+    let mut lengths = HashMap::new();
+    let mut targets = Vec::new();
+    let mut offset = 0;
+    let mut last = OpCode::Return;
+    while offset < code.len() {
+        let op = OpCode::from_byte(code[offset])
+            .ok_or_else(|| LoadError::Invalid(format!("unknown opcode {}", code[offset])))?;
+        last = op;
+        let length = match op {
+            OpCode::Constant => {
+                if operand(offset + 1)? >= chunk.constants.len() {
+                    return Err(invalid("constant index out of range"));
+                }
+                3
+            }
+            OpCode::GetGlobal
+            | OpCode::DefineGlobal
+            | OpCode::SetGlobal
+            | OpCode::GetSuper
+            | OpCode::Class
+            | OpCode::Method => {
+                name(operand(offset + 1)?)?;
+                3
+            }
+            OpCode::GetProperty | OpCode::SetProperty => {
+                name(operand(offset + 1)?)?;
+                spans(offset, 2)?;
+                3
+            }
+            OpCode::GetLocal | OpCode::SetLocal => {
+                byte(offset + 1)?;
+                2
+            }
+            OpCode::GetUpvalue | OpCode::SetUpvalue => {
+                if byte(offset + 1)? >= function.upvalue_count {
+                    return Err(invalid("upvalue index out of range"));
+                }
+                2
+            }
+            OpCode::Call => {
+                byte(offset + 1)?;
+                spans(offset, 3)?;
+                2
+            }
+            OpCode::Jump | OpCode::JumpIfFalse => {
+                targets.push(offset + 3 + operand(offset + 1)?);
+                3
+            }
+            OpCode::Loop => {
+                let target = (offset + 3)
+                    .checked_sub(operand(offset + 1)?)
+                    .ok_or_else(|| invalid("loop jumps before the start of the code"))?;
+                targets.push(target);
+                3
+            }
+            OpCode::Closure => {
+                let Some(Value::Function(nested)) = chunk.constants.get(operand(offset + 1)?)
+                else {
+                    return Err(invalid("closure operand isn't a function constant"));
+                };
+                for i in 0..nested.upvalue_count {
+                    let is_local = byte(offset + 3 + 2 * i)?;
+                    let index = byte(offset + 4 + 2 * i)?;
+                    if is_local > 1 || (is_local == 0 && index >= function.upvalue_count) {
+                        return Err(invalid("closure captures an unknown variable"));
+                    }
+                }
+                3 + 2 * nested.upvalue_count
+            }
+            _ => 1,
+        };
+        lengths.insert(offset, length);
+        offset += length;
+    }
+
+    if targets.iter().any(|target| !lengths.contains_key(target)) {
+        return Err(invalid("jump to the middle of an instruction"));
+    }
+    // the vm would run off the end of the code otherwise
+    if !matches!(last, OpCode::Return | OpCode::Jump | OpCode::Loop) {
+        return Err(invalid("code doesn't end with a return"));
+    }
+    check_stack(function, &lengths)
+}
+
+// Follows every path through the code with the height of the stack, counted from
+// the slot of the running function, and the locals closures may have captured. Paths
+// joining at an instruction must agree on the height, instructions may only take
+// values above that slot and locals have to be on the stack to be read, written or
+// captured. Captured locals leave the stack through CloseUpvalue or Return only, or
+// the upvalues pointing at them would be left behind.
+fn check_stack(function: &Function, lengths: &HashMap<usize, usize>) -> Result<(), LoadError> {
+    let code = &function.chunk.code;
+    let byte = |offset: usize| code[offset] as usize;
+    let operand = |offset: usize| u16::from_be_bytes([code[offset], code[offset + 1]]) as usize;
+
+    let mut seen: HashMap<usize, (usize, BTreeSet<usize>)> = HashMap::new();
+    // the function and its arguments are on the stack when it starts
+    let mut pending = vec![(0, 1 + function.arity, BTreeSet::new())];
+    while let Some((offset, height, mut captured)) = pending.pop() {
+        if offset >= code.len() {
+            return Err(invalid("code doesn't end with a return"));
+        }
+        // instructions are followed again until the captures of all paths to them are known
+        if let Some((known, known_captured)) = seen.get(&offset) {
+            if *known != height {
+                return Err(invalid("paths through the code leave the stack uneven"));
+            }
+            if captured.is_subset(known_captured) {
+                continue;
+            }
+            captured.extend(known_captured.iter().copied());
+        }
+        seen.insert(offset, (height, captured.clone()));
+
+        let op = OpCode::from_byte(code[offset]).expect("opcodes were checked");
+        let next = offset + lengths[&offset];
+        // values taken off the stack and put back; values only looked at count as
+        // taken and put back
+        let (taken, put) = match op {
+            OpCode::Constant
+            | OpCode::Nil
+            | OpCode::True
+            | OpCode::False
+            | OpCode::None
+            | OpCode::Uninitialized
+            | OpCode::GetGlobal
+            | OpCode::GetUpvalue
+            | OpCode::Class => (0, 1),
+            OpCode::GetLocal => {
+                if byte(offset + 1) >= height {
+                    return Err(invalid("local slot above the stack"));
+                }
+                (0, 1)
+            }
+            OpCode::Closure => {
+                let Some(Value::Function(nested)) = function.chunk.constants.get(operand(offset + 1))
+                else {
+                    unreachable!("closure operands were checked")
+                };
+                // local functions capture themselves, in the slot the closure goes to
+                let slots = height + 1;
+                for i in 0..nested.upvalue_count {
+                    let index = byte(offset + 4 + 2 * i);
+                    if byte(offset + 3 + 2 * i) == 1 {
+                        if index >= slots {
+                            return Err(invalid("local slot above the stack"));
+                        }
+                        captured.insert(index);
+                    }
+                }
+                (0, 1)
+            }
+            OpCode::SetLocal => {
+                if byte(offset + 1) >= height.saturating_sub(1) {
+                    return Err(invalid("local slot above the stack"));
+                }
+                (1, 1)
+            }
+            OpCode::Pop
+            | OpCode::DefineGlobal
+            | OpCode::Print
+            | OpCode::CloseUpvalue
+            | OpCode::Return => (1, 0),
+            OpCode::SetGlobal
+            | OpCode::SetUpvalue
+            | OpCode::GetProperty
+            | OpCode::Not
+            | OpCode::Negate
+            | OpCode::Positive
+            | OpCode::JumpIfFalse => (1, 1),
+            OpCode::SetProperty
+            | OpCode::GetSuper
+            | OpCode::Equal
+            | OpCode::NotEqual
+            | OpCode::Greater
+            | OpCode::GreaterEqual
+            | OpCode::Less
+            | OpCode::LessEqual
+            | OpCode::Add
+            | OpCode::Subtract
+            | OpCode::Multiply
+            | OpCode::Divide
+            // the class below the subclass or method stays
+            | OpCode::Inherit
+            | OpCode::Method => (2, 1),
+            OpCode::Call => (byte(offset + 1) + 1, 1),
+            OpCode::Jump | OpCode::Loop => (0, 0),
+        };
+        if taken >= height {
+            return Err(invalid(
+                "instruction takes more values than the stack holds",
+            ));
+        }
+        let height = height - taken + put;
+        match op {
+            OpCode::CloseUpvalue => {
+                captured.split_off(&height);
+            }
+            OpCode::Return => {}
+            _ if captured.range(height..).next().is_some() => {
+                return Err(invalid(
+                    "captured local taken off the stack without closing it",
+                ));
+            }
+            _ => {}
+        }
+
+        match op {
+            OpCode::Return => {}
+            OpCode::Jump => pending.push((next + operand(offset + 1), height, captured)),
+            OpCode::Loop => pending.push((next - operand(offset + 1), height, captured)),
+            OpCode::JumpIfFalse => {
+                pending.push((next + operand(offset + 1), height, captured.clone()));
+                pending.push((next, height, captured));
+            }
+            _ => pending.push((next, height, captured)),
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,302 @@
+use std::{
+    any::Any,
+    collections::HashMap,
+    fmt::{Debug, Display},
+    rc::Rc,
+};
+
+use interpreter::{
+    callable::LoxCallable,
+    convert::IntoLox,
+    result::{ForeignObject, IResult},
+};
+use rustcore::{Gc, Trace, Tracer};
+
+use crate::chunk::Chunk;
+
+#[derive(Clone)]
+pub enum Value {
+    Nil,
+    // result of an assignment, not a value
+    None,
+    // held by variables declared without an initializer; never read
+    Uninitialized,
+    Bool(bool),
+    Number(f64),
+    String(Rc<String>),
+    // a function as compiled; only found in constant pools
+    Function(Rc<Function>),
+    Closure(Gc<Closure>),
+    BoundMethod(Gc<BoundMethod>),
+    Class(Gc<Class>),
+    Instance(Gc<Instance>),
+    Native(Gc<LoxCallable>),
+    List(Rc<Vec<Value>>),
+    // a host value with no vm counterpart, such as a class of the tree-walker
+    Host(IResult),
+}
+
+pub struct Function {
+    pub name: Rc<str>,
+    pub arity: usize,
+    pub upvalue_count: usize,
+    pub chunk: Chunk,
+}
+
+pub struct Closure {
+    pub function: Rc<Function>,
+    pub upvalues: Vec<Gc<Upvalue>>,
+}
+
+// A variable captured by a closure. It points into the stack while the variable
+// is in scope and holds the value itself once the scope is closed.
+pub enum Upvalue {
+    Open(usize),
+    Closed(Value),
+}
+
+pub struct BoundMethod {
+    pub receiver: Value,
+    pub method: Gc<Closure>,
+}
+
+pub struct Class {
+    pub name: String,
+    // inherited methods are copied in when the class is declared
+    pub methods: HashMap<String, Gc<Closure>>,
+}
+
+impl Class {
+    // calling the class runs init (if any) with the call arguments
+    pub fn arity(&self) -> usize {
+        self.methods
+            .get("init")
+            .map_or(0, |init| init.function.arity)
+    }
+}
+
+pub struct Instance {
+    pub class: Gc<Class>,
+    pub fields: HashMap<String, Value>,
+}
+
+impl Trace for Closure {
+    fn trace(&self, tracer: &mut Tracer) {
+        for upvalue in self.upvalues.iter() {
+            tracer.edge(upvalue);
+        }
+    }
+}
+
+// open upvalues point into the stack, which is a root of its own
+impl Trace for Upvalue {
+    fn trace(&self, tracer: &mut Tracer) {
+        if let Self::Closed(value) = self {
+            value.trace(tracer);
+        }
+    }
+}
+
+impl Trace for BoundMethod {
+    fn trace(&self, tracer: &mut Tracer) {
+        self.receiver.trace(tracer);
+        tracer.edge(&self.method);
+    }
+}
+
+impl Trace for Class {
+    fn trace(&self, tracer: &mut Tracer) {
+        for method in self.methods.values() {
+            tracer.edge(method);
+        }
+    }
+}
+
+impl Trace for Instance {
+    fn trace(&self, tracer: &mut Tracer) {
+        tracer.edge(&self.class);
+        for value in self.fields.values() {
+            value.trace(tracer);
+        }
+    }
+}
+
+// lists are shared and so never looked into, like those of the tree-walker
+impl Trace for Value {
+    fn trace(&self, tracer: &mut Tracer) {
+        match self {
+            Self::Closure(closure) => tracer.edge(closure),
+            Self::BoundMethod(bound) => tracer.edge(bound),
+            Self::Class(class) => tracer.edge(class),
+            Self::Instance(instance) => tracer.edge(instance),
+            Self::Native(native) => tracer.edge(native),
+            Self::Host(value) => value.trace(tracer),
+            _ => {}
+        }
+    }
+}
+
+impl Value {
+    // nil and false are falsey, every other value is truthy
+    #[inline]
+    pub fn is_truthy(&self) -> bool {
+        !matches!(self, Self::Nil | Self::Bool(false) | Self::None)
+    }
+
+    // name of the type of the value, as reported to scripts
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Self::Number(_) => "number",
+            Self::String(_) => "string",
+            Self::Bool(_) => "bool",
+            Self::Function(_) | Self::Closure(_) | Self::BoundMethod(_) | Self::Native(_) => {
+                "function"
+            }
+            Self::Class(_) => "class",
+            Self::Instance(_) => "instance",
+            Self::List(_) => "list",
+            Self::Host(value) => value.type_name(),
+            Self::Nil | Self::None | Self::Uninitialized => "nil",
+        }
+    }
+
+    // number of arguments a callable value takes; None for values that can't be called
+    pub fn arity(&self) -> Option<usize> {
+        match self {
+            Self::Closure(closure) => Some(closure.function.arity),
+            Self::BoundMethod(bound) => Some(bound.method.function.arity),
+            Self::Class(class) => Some(class.arity()),
+            Self::Native(native) if native.native().is_some() => Some(native.arity),
+            _ => Option::None,
+        }
+    }
+}
+
+// the same as the tree-walker: functions are never equal, classes and instances
+// are equal to themselves only
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Nil, Self::Nil) | (Self::None, Self::None) => true,
+            (Self::Bool(a), Self::Bool(b)) => a == b,
+            (Self::Number(a), Self::Number(b)) => a == b,
+            (Self::String(a), Self::String(b)) => a == b,
+            (Self::Class(a), Self::Class(b)) => Gc::ptr_eq(a, b),
+            (Self::Instance(a), Self::Instance(b)) => Gc::ptr_eq(a, b),
+            (Self::List(a), Self::List(b)) => a == b,
+            (Self::Host(a), Self::Host(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Nil | Self::Uninitialized => write!(f, "nil"),
+            Self::None => write!(f, ""),
+            Self::Bool(b) => write!(f, "{}", b),
+            Self::Number(n) => write!(f, "{}", n),
+            Self::String(s) => write!(f, "{}", s),
+            Self::Function(function) => {
+                write!(f, "LoxCallable {{ arity: {}, call: fn }}", function.arity)
+            }
+            Self::Closure(closure) => {
+                write!(
+                    f,
+                    "LoxCallable {{ arity: {}, call: fn }}",
+                    closure.function.arity
+                )
+            }
+            Self::BoundMethod(bound) => write!(
+                f,
+                "LoxCallable {{ arity: {}, call: fn }}",
+                bound.method.function.arity
+            ),
+            Self::Native(native) => write!(f, "{}", native),
+            Self::Class(class) => write!(f, "{}", class.name),
+            Self::Instance(instance) => write!(f, "{} instance", instance.class.name),
+            Self::List(items) => {
+                write!(f, "[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                write!(f, "]")
+            }
+            Self::Host(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+impl Debug for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::String(s) => write!(f, "{:?}", s),
+            Self::Function(function) => write!(f, "<fn {}>", function.name),
+            other => write!(f, "{}", other),
+        }
+    }
+}
+
+// A vm object handed to native functions or to the host. It goes back to being
+// the same object when it is passed back to the vm.
+struct VmObject(Value);
+
+impl ForeignObject for VmObject {
+    fn type_name(&self) -> &'static str {
+        self.0.type_name()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl Display for VmObject {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl IntoLox for Value {
+    fn into_lox(self) -> IResult {
+        match self {
+            Self::Nil | Self::Uninitialized => IResult::Nil,
+            Self::None => IResult::None,
+            Self::Bool(b) => IResult::Bool(b),
+            Self::Number(n) => IResult::Number(n),
+            Self::String(s) => IResult::String(s),
+            Self::Native(native) => IResult::Callable(native),
+            Self::List(items) => IResult::List(Rc::new(
+                items.iter().cloned().map(Value::into_lox).collect(),
+            )),
+            Self::Host(value) => value,
+            object => IResult::Foreign(Rc::new(VmObject(object))),
+        }
+    }
+}
+
+// values coming from natives or from the host
+impl From<IResult> for Value {
+    fn from(value: IResult) -> Self {
+        match value {
+            IResult::Nil => Self::Nil,
+            IResult::None => Self::None,
+            IResult::Bool(b) => Self::Bool(b),
+            IResult::Number(n) => Self::Number(n),
+            IResult::String(s) => Self::String(s),
+            IResult::Callable(callable) if callable.native().is_some() => Self::Native(callable),
+            IResult::List(items) => {
+                Self::List(Rc::new(items.iter().cloned().map(Value::from).collect()))
+            }
+            IResult::Foreign(object) => match object.as_any().downcast_ref::<VmObject>() {
+                Some(VmObject(value)) => value.clone(),
+                Option::None => Self::Host(IResult::Foreign(object)),
+            },
+            other => Self::Host(other),
+        }
+    }
+}
@@ -0,0 +1,782 @@
+pub mod chunk;
+pub mod compiler;
+pub mod disasm;
+pub mod serialize;
+pub mod value;
+
+use std::{
+    collections::HashMap,
+    io::{stdout, Stdout, Write},
+    rc::Rc,
+};
+
+use chunk::OpCode;
+use interpreter::{
+    convert::IntoLox,
+    error::{CallFrame as Frame, RuntimeError, RuntimeErrorKind},
+    foreignf::NativeRegistry,
+    interrupt::InterruptHandle,
+    limits::{Budget, Limits, OBJECT_SIZE},
+    FRAMES_MAX,
+};
+use rustcore::{Gc, Shared};
+use scanner::tokens::Span;
+use value::{BoundMethod, Class, Closure, Function, Instance, Upvalue, Value};
+
+// a running call: the closure, the next instruction to run and where its slots
+// start on the stack
+struct CallFrame {
+    closure: Gc<Closure>,
+    ip: usize,
+    base: usize,
+    // shown in backtraces: the function, or the class when running its initializer
+    name: Rc<str>,
+}
+
+// where a call comes from, to point errors at it; all default for calls made by
+// the host
+#[derive(Clone, Copy, Default)]
+struct CallSite {
+    call: Span,
+    callee: Span,
+    paren: Span,
+}
+
+// A stack machine running the bytecode of `compiler::Compiler`. It is a drop-in
+// replacement for the tree-walker: same output, same errors, same backtraces.
+pub struct Vm<T: Write> {
+    stack: Vec<Value>,
+    frames: Vec<CallFrame>,
+    globals: HashMap<String, Value>,
+    // upvalues still pointing into the stack
+    open_upvalues: Vec<Gc<Upvalue>>,
+    ostream: Shared<T>,
+    budget: Budget,
+}
+
+impl Default for Vm<Stdout> {
+    fn default() -> Self {
+        Vm::new_with_out(Shared::new(stdout()))
+    }
+}
+
+impl<T: Write> Vm<T> {
+    pub fn new_with_out(ostream: Shared<T>) -> Vm<T> {
+        Vm::new_with_registry(ostream, NativeRegistry::default())
+    }
+
+    // a vm whose globals start out with the functions of the registry
+    pub fn new_with_registry(ostream: Shared<T>, natives: NativeRegistry) -> Vm<T> {
+        let globals = natives
+            .into_functions()
+            .map(|(name, callable)| (name, Value::Native(Gc::new(callable))))
+            .collect();
+        Vm {
+            stack: Vec::new(),
+            frames: Vec::new(),
+            globals,
+            open_upvalues: Vec::new(),
+            ostream,
+            budget: Budget::default(),
+        }
+    }
+
+    // the limits apply from the next run on
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.budget.limits = limits;
+    }
+
+    // for stopping runs from another thread
+    pub fn interrupt_handle(&self) -> InterruptHandle {
+        self.budget.interrupt.clone()
+    }
+
+    pub fn get_global(&self, name: &str) -> Option<Value> {
+        self.globals.get(name).cloned()
+    }
+
+    pub fn set_global(&mut self, name: &str, value: Value) {
+        self.globals.insert(name.to_string(), value);
+    }
+
+    // runs a compiled script; its result is the value of its last expression statement
+    pub fn interpret(&mut self, script: Rc<Function>) -> Result<Value, RuntimeError> {
+        let closure = Gc::new(Closure {
+            function: script,
+            upvalues: Vec::new(),
+        });
+        self.budget.reset();
+        self.stack.push(Value::Closure(closure.clone()));
+        self.frames.push(CallFrame {
+            closure,
+            ip: 0,
+            base: 0,
+            name: "<script>".into(),
+        });
+        self.finish(0)
+    }
+
+    // calls a function or class value from outside of any script
+    pub fn call(&mut self, callee: Value, arguments: Vec<Value>) -> Result<Value, RuntimeError> {
+        let Some(arity) = callee.arity() else {
+            return Err(RuntimeError::native(
+                RuntimeErrorKind::NotCallable,
+                format!("{} is not callable", callee).as_str(),
+            ));
+        };
+        if arguments.len() != arity {
+            return Err(RuntimeError::native(
+                RuntimeErrorKind::ArityMismatch,
+                format!("expected {} arguments but got {}", arity, arguments.len()).as_str(),
+            ));
+        }
+
+        let depth = self.frames.len();
+        if depth == 0 {
+            self.budget.reset();
+        }
+        let argc = arguments.len();
+        self.stack.push(callee);
+        self.stack.extend(arguments);
+        if let Err(err) = self.call_value(argc, CallSite::default()) {
+            self.reset(depth, self.stack.len() - argc - 1);
+            return Err(err);
+        }
+        if self.frames.len() == depth {
+            // natives and classes without initializer are done already
+            return Ok(self.stack.pop().expect("the call left its result"));
+        }
+        self.finish(depth)
+    }
+
+    // runs the frames above depth and pops their result
+    fn finish(&mut self, depth: usize) -> Result<Value, RuntimeError> {
+        let base = self.frames[depth].base;
+        match self.run(depth) {
+            Ok(()) => Ok(self.stack.pop().expect("the call left its result")),
+            Err(err) => {
+                self.reset(depth, base);
+                Err(err)
+            }
+        }
+    }
+
+    // drops what is left of the calls above depth after an error
+    fn reset(&mut self, depth: usize, base: usize) {
+        // the run failed already, dropped variables or not
+        self.close_upvalues(base);
+        self.frames.truncate(depth);
+        self.stack.truncate(base);
+    }
+
+    fn frame(&mut self) -> &mut CallFrame {
+        self.frames.last_mut().expect("a function is running")
+    }
+
+    #[inline]
+    fn read_byte(&mut self) -> u8 {
+        let frame = self.frame();
+        let byte = frame.closure.function.chunk.code[frame.ip];
+        frame.ip += 1;
+        byte
+    }
+
+    #[inline]
+    fn read_u16(&mut self) -> u16 {
+        let frame = self.frame();
+        let value = frame.closure.function.chunk.read_u16(frame.ip);
+        frame.ip += 2;
+        value
+    }
+
+    fn read_constant(&mut self) -> Value {
+        let index = self.read_u16() as usize;
+        self.frame().closure.function.chunk.constants[index].clone()
+    }
+
+    fn read_name(&mut self) -> Rc<String> {
+        match self.read_constant() {
+            Value::String(name) => name,
+            other => unreachable!("name constants are strings, found {:?}", other),
+        }
+    }
+
+    fn pop(&mut self) -> Value {
+        self.stack.pop().expect("the compiler balances the stack")
+    }
+
+    fn peek(&self, distance: usize) -> &Value {
+        &self.stack[self.stack.len() - 1 - distance]
+    }
+
+    // the span of the instruction starting at offset in the running function
+    fn span_at(&self, offset: usize) -> Span {
+        let frame = self.frames.last().expect("a function is running");
+        frame.closure.function.chunk.span(offset)
+    }
+
+    // an error raised by the instruction starting at offset, with the calls it was
+    // raised in
+    fn error(&self, span: Span, kind: RuntimeErrorKind, message: &str) -> RuntimeError {
+        self.with_backtrace(RuntimeError::native(kind, message).with_span(span))
+    }
+
+    fn with_backtrace(&self, mut err: RuntimeError) -> RuntimeError {
+        if err.backtrace.is_empty() {
+            let innermost = self.frames.len().saturating_sub(1);
+            err.backtrace = self
+                .frames
+                .iter()
+                .enumerate()
+                .rev()
+                .map(|(i, frame)| Frame {
+                    function: frame.name.to_string(),
+                    line: if i == innermost {
+                        err.span.line
+                    } else {
+                        // callers are stopped right after their two byte call instruction
+                        frame.closure.function.chunk.span(frame.ip - 2).line
+                    },
+                })
+                .collect();
+        }
+        err
+    }
+
+    fn run(&mut self, depth: usize) -> Result<(), RuntimeError> {
+        loop {
+            let offset = self.frame().ip;
+            let byte = self.read_byte();
+            let op = OpCode::from_byte(byte).expect("the compiler emits valid instructions");
+            match op {
+                OpCode::Constant => {
+                    let value = self.read_constant();
+                    self.stack.push(value);
+                }
+                OpCode::Nil => self.stack.push(Value::Nil),
+                OpCode::True => self.stack.push(Value::Bool(true)),
+                OpCode::False => self.stack.push(Value::Bool(false)),
+                OpCode::None => self.stack.push(Value::None),
+                OpCode::Uninitialized => self.stack.push(Value::Uninitialized),
+                OpCode::Pop => {
+                    self.pop();
+                }
+                OpCode::GetLocal => {
+                    let slot = self.read_byte() as usize;
+                    let base = self.frame().base;
+                    let value = self.stack[base + slot].clone();
+                    self.push_initialized(value, offset)?;
+                }
+                OpCode::SetLocal => {
+                    let slot = self.read_byte() as usize;
+                    let base = self.frame().base;
+                    self.stack[base + slot] = self.pop();
+                    self.stack.push(Value::None);
+                }
+                OpCode::GetGlobal => {
+                    let name = self.read_name();
+                    match self.globals.get(name.as_str()) {
+                        Some(value) => {
+                            let value = value.clone();
+                            self.push_initialized(value, offset)?;
+                        }
+                        None => {
+                            return Err(self.error(
+                                self.span_at(offset),
+                                RuntimeErrorKind::UndefinedVariable,
+                                format!("variable {} not in scope", name).as_str(),
+                            ))
+                        }
+                    }
+                }
+                OpCode::DefineGlobal => {
+                    let name = self.read_name();
+                    let value = self.pop();
+                    self.globals.insert(name.to_string(), value);
+                }
+                OpCode::SetGlobal => {
+                    let name = self.read_name();
+                    let value = self.pop();
+                    match self.globals.get_mut(name.as_str()) {
+                        Some(global) => *global = value,
+                        None => {
+                            return Err(self.error(
+                                self.span_at(offset),
+                                RuntimeErrorKind::UndefinedVariable,
+                                format!("{} is not binded", name).as_str(),
+                            ))
+                        }
+                    }
+                    self.stack.push(Value::None);
+                }
+                OpCode::GetUpvalue => {
+                    let index = self.read_byte() as usize;
+                    let upvalue = self.frame().closure.upvalues[index].clone();
+                    let value = match &*upvalue.borrow() {
+                        Upvalue::Open(slot) => match self.stack.get(*slot) {
+                            Some(value) => value.clone(),
+                            None => return Err(self.dropped_upvalue(offset)),
+                        },
+                        Upvalue::Closed(value) => value.clone(),
+                    };
+                    self.push_initialized(value, offset)?;
+                }
+                OpCode::SetUpvalue => {
+                    let index = self.read_byte() as usize;
+                    let value = self.pop();
+                    let upvalue = self.frame().closure.upvalues[index].clone();
+                    match &mut *upvalue.borrow_mut() {
+                        Upvalue::Open(slot) => match self.stack.get_mut(*slot) {
+                            Some(local) => *local = value,
+                            None => return Err(self.dropped_upvalue(offset)),
+                        },
+                        Upvalue::Closed(closed) => *closed = value,
+                    }
+                    self.stack.push(Value::None);
+                }
+                OpCode::GetProperty => {
+                    let name = self.read_name();
+                    let object = self.pop();
+                    let Value::Instance(instance) = &object else {
+                        let chunk = &self.frames.last().unwrap().closure.function.chunk;
+                        return Err(self.error(
+                            chunk.extra_span(offset, 1),
+                            RuntimeErrorKind::NotAnInstance,
+                            "only instances have properties",
+                        ));
+                    };
+                    if let Some(value) = instance.fields.get(name.as_str()) {
+                        let value = value.clone();
+                        self.stack.push(value);
+                        continue;
+                    }
+                    let method = instance.class.methods.get(name.as_str()).cloned();
+                    match method {
+                        Some(method) => {
+                            self.allocate(OBJECT_SIZE, offset)?;
+                            self.stack.push(Value::BoundMethod(Gc::new(BoundMethod {
+                                receiver: object,
+                                method,
+                            })))
+                        }
+                        None => {
+                            return Err(self.error(
+                                self.span_at(offset),
+                                RuntimeErrorKind::UndefinedProperty,
+                                format!("undefined property '{}'", name).as_str(),
+                            ))
+                        }
+                    }
+                }
+                OpCode::SetProperty => {
+                    let name = self.read_name();
+                    let value = self.pop();
+                    let object = self.pop();
+                    let Value::Instance(instance) = object else {
+                        let chunk = &self.frames.last().unwrap().closure.function.chunk;
+                        return Err(self.error(
+                            chunk.extra_span(offset, 1),
+                            RuntimeErrorKind::NotAnInstance,
+                            "only instances have fields",
+                        ));
+                    };
+                    instance
+                        .borrow_mut()
+                        .fields
+                        .insert(name.to_string(), value.clone());
+                    self.stack.push(value);
+                }
+                OpCode::GetSuper => {
+                    let name = self.read_name();
+                    let Value::Class(superclass) = self.pop() else {
+                        return Err(self.invalid_bytecode(offset, "'super' isn't bound to a class"));
+                    };
+                    let receiver = self.pop();
+                    match superclass.methods.get(name.as_str()) {
+                        Some(method) => {
+                            self.allocate(OBJECT_SIZE, offset)?;
+                            self.stack.push(Value::BoundMethod(Gc::new(BoundMethod {
+                                receiver,
+                                method: method.clone(),
+                            })))
+                        }
+                        None => {
+                            return Err(self.error(
+                                self.span_at(offset),
+                                RuntimeErrorKind::UndefinedProperty,
+                                format!("undefined property '{}'", name).as_str(),
+                            ))
+                        }
+                    }
+                }
+                OpCode::Equal => {
+                    let right = self.pop();
+                    let left = self.pop();
+                    self.stack.push(Value::Bool(left == right));
+                }
+                OpCode::NotEqual => {
+                    let right = self.pop();
+                    let left = self.pop();
+                    self.stack.push(Value::Bool(left != right));
+                }
+                OpCode::Add => {
+                    let right = self.pop();
+                    let left = self.pop();
+                    let result = match (&left, &right) {
+                        (Value::Number(left), Value::Number(right)) => Value::Number(left + right),
+                        (Value::String(left), Value::String(right)) => {
+                            self.allocate(left.len() + right.len(), offset)?;
+                            Value::String(Rc::new(format!("{}{}", left, right)))
+                        }
+                        _ => {
+                            return Err(self.error(
+                                self.span_at(offset),
+                                RuntimeErrorKind::InvalidOperand,
+                                "invalid operands for plus operator",
+                            ))
+                        }
+                    };
+                    self.stack.push(result);
+                }
+                OpCode::Subtract => {
+                    let (left, right) = self.number_operands(offset, "minus operator")?;
+                    self.stack.push(Value::Number(left - right));
+                }
+                OpCode::Multiply => {
+                    let (left, right) = self.number_operands(offset, "star operator")?;
+                    self.stack.push(Value::Number(left * right));
+                }
+                OpCode::Divide => {
+                    let (left, right) = self.number_operands(offset, "slash operator")?;
+                    self.stack.push(Value::Number(left / right));
+                }
+                OpCode::Greater => {
+                    let (left, right) = self.number_operands(offset, "greater operator")?;
+                    self.stack.push(Value::Bool(left > right));
+                }
+                OpCode::GreaterEqual => {
+                    let (left, right) = self.number_operands(offset, "greater-equal")?;
+                    self.stack.push(Value::Bool(left >= right));
+                }
+                OpCode::Less => {
+                    let (left, right) = self.number_operands(offset, "less")?;
+                    self.stack.push(Value::Bool(left < right));
+                }
+                OpCode::LessEqual => {
+                    let (left, right) = self.number_operands(offset, "less-equal")?;
+                    self.stack.push(Value::Bool(left <= right));
+                }
+                OpCode::Not => {
+                    let value = self.pop();
+                    self.stack.push(Value::Bool(!value.is_truthy()));
+                }
+                OpCode::Negate | OpCode::Positive => {
+                    let Value::Number(value) = self.pop() else {
+                        return Err(self.error(
+                            self.span_at(offset),
+                            RuntimeErrorKind::InvalidOperand,
+                            "invalid operand for plus/minus operator",
+                        ));
+                    };
+                    let sign = if op == OpCode::Negate { -1.0 } else { 1.0 };
+                    self.stack.push(Value::Number(sign * value));
+                }
+                OpCode::Print => {
+                    let value = self.pop();
+                    if let Err(err) = writeln!(self.ostream.borrow_mut(), "{}", value) {
+                        return Err(self.error(
+                            self.span_at(offset),
+                            RuntimeErrorKind::Io,
+                            format!("failed to write to output stream: {:?}", err).as_str(),
+                        ));
+                    }
+                }
+                OpCode::Jump => {
+                    let distance = self.read_u16() as usize;
+                    self.frame().ip += distance;
+                }
+                OpCode::JumpIfFalse => {
+                    let distance = self.read_u16() as usize;
+                    if !self.peek(0).is_truthy() {
+                        self.frame().ip += distance;
+                    }
+                }
+                OpCode::Loop => {
+                    let distance = self.read_u16() as usize;
+                    self.frame().ip -= distance;
+                    self.budget
+                        .step()
+                        .map_err(|err| self.with_backtrace(err.with_span(self.span_at(offset))))?;
+                }
+                OpCode::Call => {
+                    let argc = self.read_byte() as usize;
+                    let chunk = &self.frames.last().unwrap().closure.function.chunk;
+                    let site = CallSite {
+                        call: chunk.span(offset),
+                        callee: chunk.extra_span(offset, 1),
+                        paren: chunk.extra_span(offset, 2),
+                    };
+                    self.call_value(argc, site)?;
+                }
+                OpCode::Closure => {
+                    let Value::Function(function) = self.read_constant() else {
+                        unreachable!("closures are made of function constants")
+                    };
+                    // methods are made with their class, as on the tree-walker
+                    let frame = self.frames.last().unwrap();
+                    let code = &frame.closure.function.chunk.code;
+                    let is_method =
+                        code[frame.ip + 2 * function.upvalue_count] == OpCode::Method as u8;
+                    if !is_method {
+                        self.allocate(OBJECT_SIZE, offset)?;
+                    }
+                    let mut upvalues = Vec::with_capacity(function.upvalue_count);
+                    for _ in 0..function.upvalue_count {
+                        let is_local = self.read_byte() == 1;
+                        let index = self.read_byte() as usize;
+                        if is_local {
+                            let slot = self.frame().base + index;
+                            upvalues.push(self.capture_upvalue(slot, offset)?);
+                        } else {
+                            upvalues.push(self.frame().closure.upvalues[index].clone());
+                        }
+                    }
+                    self.stack
+                        .push(Value::Closure(Gc::new(Closure { function, upvalues })));
+                }
+                OpCode::CloseUpvalue => {
+                    if !self.close_upvalues(self.stack.len() - 1) {
+                        return Err(self.dropped_upvalue(offset));
+                    }
+                    self.pop();
+                }
+                OpCode::Return => {
+                    let result = self.pop();
+                    let base = self.frame().base;
+                    if !self.close_upvalues(base) {
+                        return Err(self.dropped_upvalue(offset));
+                    }
+                    let frame = self.frames.pop().expect("a function is running");
+                    self.stack.truncate(frame.base);
+                    self.stack.push(result);
+                    if self.frames.len() == depth {
+                        return Ok(());
+                    }
+                }
+                OpCode::Class => {
+                    let name = self.read_name();
+                    self.allocate(OBJECT_SIZE, offset)?;
+                    self.stack.push(Value::Class(Gc::new(Class {
+                        name: name.to_string(),
+                        methods: HashMap::new(),
+                    })));
+                }
+                OpCode::Inherit => {
+                    let Value::Class(subclass) = self.pop() else {
+                        return Err(self.invalid_bytecode(offset, "only classes inherit"));
+                    };
+                    let Value::Class(superclass) = self.peek(0) else {
+                        return Err(self.error(
+                            self.span_at(offset),
+                            RuntimeErrorKind::InvalidSuperclass,
+                            "superclass must be a class",
+                        ));
+                    };
+                    let methods = superclass.methods.clone();
+                    subclass.borrow_mut().methods.extend(methods);
+                }
+                OpCode::Method => {
+                    let name = self.read_name();
+                    let Value::Closure(method) = self.pop() else {
+                        return Err(self.invalid_bytecode(offset, "methods are closures"));
+                    };
+                    let Value::Class(class) = self.peek(0) else {
+                        return Err(self.invalid_bytecode(offset, "only classes have methods"));
+                    };
+                    class.borrow_mut().methods.insert(name.to_string(), method);
+                }
+            }
+        }
+    }
+
+    // Values of the wrong type where the compiler always puts a class or method. The
+    // checks made while loading compiled scripts cover the shape of the stack, not
+    // what it holds.
+    fn invalid_bytecode(&self, offset: usize, message: &str) -> RuntimeError {
+        self.error(
+            self.span_at(offset),
+            RuntimeErrorKind::InvalidBytecode,
+            message,
+        )
+    }
+
+    // a captured variable taken off the stack without being closed, which the
+    // compiler never does
+    fn dropped_upvalue(&self, offset: usize) -> RuntimeError {
+        self.invalid_bytecode(offset, "captured variable is no longer on the stack")
+    }
+
+    // counts memory taken by the instruction at offset against the memory limit
+    fn allocate(&mut self, bytes: usize, offset: usize) -> Result<(), RuntimeError> {
+        self.budget
+            .allocate(bytes)
+            .map_err(|err| self.with_backtrace(err.with_span(self.span_at(offset))))
+    }
+
+    // reading a variable declared without an initializer is an error
+    fn push_initialized(&mut self, value: Value, offset: usize) -> Result<(), RuntimeError> {
+        if let Value::Uninitialized = value {
+            return Err(self.error(
+                self.span_at(offset),
+                RuntimeErrorKind::UninitializedVariable,
+                "variable is not initialized",
+            ));
+        }
+        self.stack.push(value);
+        Ok(())
+    }
+
+    fn number_operands(
+        &mut self,
+        offset: usize,
+        operator: &str,
+    ) -> Result<(f64, f64), RuntimeError> {
+        let right = self.pop();
+        let left = self.pop();
+        match (left, right) {
+            (Value::Number(left), Value::Number(right)) => Ok((left, right)),
+            _ => Err(self.error(
+                self.span_at(offset),
+                RuntimeErrorKind::InvalidOperand,
+                format!("invalid operands for {}", operator).as_str(),
+            )),
+        }
+    }
+
+    // calls the callee below the argc arguments on top of the stack. Functions get a
+    // frame, to be run by the caller; natives and classes without initializer leave
+    // their result in place of the callee right away.
+    fn call_value(&mut self, argc: usize, site: CallSite) -> Result<(), RuntimeError> {
+        let base = self.stack.len() - argc - 1;
+        let callee = self.stack[base].clone();
+        let Some(arity) = callee.arity() else {
+            return Err(self.error(
+                site.callee,
+                RuntimeErrorKind::NotCallable,
+                format!("{} is not callable", callee).as_str(),
+            ));
+        };
+        if argc != arity {
+            return Err(self.error(
+                site.paren,
+                RuntimeErrorKind::ArityMismatch,
+                format!("expected {} arguments but got {}", arity, argc).as_str(),
+            ));
+        }
+        let over_budget =
+            |vm: &Self, err: RuntimeError| vm.with_backtrace(err.with_span(site.call));
+        self.budget.step().map_err(|err| over_budget(self, err))?;
+        if let Value::Class(_) = callee {
+            // the instance is made before init is entered
+            self.budget
+                .allocate(OBJECT_SIZE)
+                .map_err(|err| over_budget(self, err))?;
+        }
+        if !matches!(callee, Value::Native(_)) {
+            let entered = self.budget.enter(self.frames.len() + 1);
+            entered.map_err(|err| over_budget(self, err))?;
+            if self.frames.len() == FRAMES_MAX {
+                return Err(self.error(
+                    site.call,
+                    RuntimeErrorKind::StackOverflow,
+                    "stack overflow",
+                ));
+            }
+        }
+
+        match callee {
+            Value::Closure(closure) => {
+                let name = closure.function.name.clone();
+                self.push_frame(closure, base, name);
+            }
+            Value::BoundMethod(bound) => {
+                self.stack[base] = bound.receiver.clone();
+                let name = bound.method.function.name.clone();
+                self.push_frame(bound.method.clone(), base, name);
+            }
+            Value::Class(class) => {
+                let init = class.methods.get("init").cloned();
+                let name: Rc<str> = class.name.as_str().into();
+                self.stack[base] = Value::Instance(Gc::new(Instance {
+                    class,
+                    fields: HashMap::new(),
+                }));
+                if let Some(init) = init {
+                    self.push_frame(init, base, name);
+                }
+            }
+            Value::Native(native) => {
+                let function = native.native().cloned().expect("only natives are callable");
+                let arguments = self.stack.drain(base + 1..).map(Value::into_lox).collect();
+                let result = function(arguments).map_err(|mut err| {
+                    // spans of real tokens start at line 1
+                    if err.span.line == 0 {
+                        err.span = site.call;
+                    }
+                    self.with_backtrace(err)
+                })?;
+                self.stack[base] = Value::from(result);
+            }
+            _ => unreachable!("callee was checked to be callable"),
+        }
+        Ok(())
+    }
+
+    fn push_frame(&mut self, closure: Gc<Closure>, base: usize, name: Rc<str>) {
+        self.frames.push(CallFrame {
+            closure,
+            ip: 0,
+            base,
+            name,
+        });
+    }
+
+    // the upvalue pointing at the stack slot, shared by all closures capturing it. The
+    // slot may be the one the closure made by the instruction at offset goes to.
+    fn capture_upvalue(&mut self, slot: usize, offset: usize) -> Result<Gc<Upvalue>, RuntimeError> {
+        if slot > self.stack.len() {
+            return Err(self.invalid_bytecode(offset, "captured variable isn't on the stack"));
+        }
+        let existing = self
+            .open_upvalues
+            .iter()
+            .find(|upvalue| matches!(&*upvalue.borrow(), Upvalue::Open(open) if *open == slot));
+        if let Some(upvalue) = existing {
+            return Ok(upvalue.clone());
+        }
+        let upvalue = Gc::new(Upvalue::Open(slot));
+        self.open_upvalues.push(upvalue.clone());
+        Ok(upvalue)
+    }
+
+    // moves the variables at slot and above off the stack, into their upvalues. False
+    // when some were taken off the stack without being closed; their upvalues are
+    // left nil.
+    fn close_upvalues(&mut self, slot: usize) -> bool {
+        let stack = &self.stack;
+        let mut on_stack = true;
+        self.open_upvalues.retain(|upvalue| {
+            let mut upvalue = upvalue.borrow_mut();
+            match &*upvalue {
+                Upvalue::Open(open) if *open >= slot => {
+                    let value = stack.get(*open).cloned();
+                    on_stack &= value.is_some();
+                    *upvalue = Upvalue::Closed(value.unwrap_or(Value::Nil));
+                    false
+                }
+                _ => true,
+            }
+        });
+        on_stack
+    }
+}